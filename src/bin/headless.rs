@@ -0,0 +1,770 @@
+//! Runs a simulation without a window, writing snapshots and metrics to disk as CSV.
+//!
+//! ```text
+//! n-body-headless --scenario pluto-and-charon --duration 5.4e6 --dt 600 --output run
+//! ```
+//!
+//! With `--checkpoint`, the full state of the run is saved every so often. A run that stopped can
+//! then carry on with `--resume`, which cuts the files it had already written back to where they
+//! stood at the checkpoint and carries on writing them from there.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+use std::fs::{create_dir_all, File, OpenOptions};
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use rand::distributions::Uniform;
+use rand_chacha::ChaCha8Rng;
+
+use n_body_rust::persistence::PersistenceError;
+use n_body_rust::universes::{BoxedVector2DDistribution, CircularVector2DDistribution};
+use n_body_rust::{persistence, universes};
+use n_body_rust::{
+    AnyCollisionPolicy, AnyForceSolver, AnyNumericalMethod, BarnesHut, Bounce, Checkpoint,
+    CollisionEvent, DirectSummation, DormandPrince, Energy, EulerMethod, Gravity, Leapfrog, Mass,
+    Merge, PassThrough, RungeKutta4, Scalar, Softening, SubSteps, TemporalDuration, Universe,
+};
+
+const USAGE: &str = "Usage: n-body-headless [options]
+
+Options:
+    --scenario <pluto-and-charon|random|plummer>
+                                           Initial universe (default: pluto-and-charon)
+    --load <file.json|file.ron>            Load the initial universe from a file instead
+    --bodies <n>                           Number of bodies in a generated scenario (default: 300)
+    --seed <n>                             Seed for a generated scenario (default: chosen at random)
+    --total-mass <kg>                      Mass of the Plummer sphere (default: 1e24)
+    --scale-radius <metres>                Scale radius of the Plummer sphere (default: 1e8)
+    --density <kg/m³>                      Density of generated bodies (default: 1400)
+    --duration <seconds>                   Simulated time to run for, a whole number of steps
+                                           (required)
+    --dt <seconds>                         Simulated time per step (required)
+    --integrator <euler|leapfrog|rk4|dormand-prince>
+                                           Numerical method (default: leapfrog)
+    --position-tolerance <metres>          Dormand-Prince absolute tolerance on positions
+                                           (default: 1)
+    --velocity-tolerance <m/s>             Dormand-Prince absolute tolerance on velocities
+                                           (default: 1e-3)
+    --relative-tolerance <value>           Dormand-Prince relative tolerance (default: 1e-6)
+    --solver <direct|barnes-hut>           Force solver (default: direct)
+    --opening-angle <theta>                Barnes-Hut opening angle (default: 0.5)
+    --collisions <merge|bounce|pass-through>
+                                           What happens when bodies touch (default: merge)
+    --restitution <value>                  Coefficient of restitution when bouncing (default: 1)
+    --softening <metres>                   Plummer softening length (default: the universe's)
+    --snapshot-every <steps>               Steps between snapshots (default: 1)
+    --output <directory>                   Where to write snapshots.csv and metrics.csv (default: .)
+    --save <file.json|file.ron>            Save the final universe to a file
+    --collision-log <file.csv|file.jsonl>  Record every collision to a file
+    --checkpoint <file.json|file.ron>      Save the full state of the run to a file as it goes
+    --checkpoint-every <steps>             Steps between checkpoints (default: 1000)
+    --resume <file.json|file.ron>          Carry on from a checkpoint until --duration, in place
+                                           of the scenario, integrator, solver, collision and dt options";
+
+const SNAPSHOTS: &str = "snapshots.csv";
+const METRICS: &str = "metrics.csv";
+
+enum ScenarioChoice {
+    PlutoAndCharon,
+    Random,
+    Plummer,
+    File(PathBuf),
+}
+
+enum IntegratorChoice {
+    Euler,
+    Leapfrog,
+    RungeKutta4,
+    DormandPrince,
+}
+
+enum CollisionChoice {
+    Merge,
+    Bounce,
+    PassThrough,
+}
+
+enum SolverChoice {
+    Direct,
+    BarnesHut,
+}
+
+struct Options {
+    scenario: ScenarioChoice,
+    bodies: u16,
+    seed: Option<u64>,
+    total_mass: Mass,
+    scale_radius: Scalar,
+    density: Scalar,
+    duration: TemporalDuration,
+    dt: TemporalDuration,
+    integrator: IntegratorChoice,
+    position_tolerance: Scalar,
+    velocity_tolerance: Scalar,
+    relative_tolerance: Scalar,
+    solver: SolverChoice,
+    opening_angle: Scalar,
+    collisions: CollisionChoice,
+    restitution: Scalar,
+    softening: Option<Softening>,
+    snapshot_every: u64,
+    output: PathBuf,
+    save: Option<PathBuf>,
+    collision_log: Option<PathBuf>,
+    checkpoint: Option<PathBuf>,
+    checkpoint_every: u64,
+    resume: Option<PathBuf>,
+}
+
+#[derive(Debug)]
+enum HeadlessError {
+    Usage(String),
+    Io(std::io::Error),
+    Persistence(PersistenceError),
+    /// The numerical method could not keep within its tolerances beyond this age.
+    Integration(TemporalDuration),
+}
+
+impl Display for HeadlessError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HeadlessError::Usage(message) => write!(f, "{}\n\n{}", message, USAGE),
+            HeadlessError::Io(error) => Display::fmt(error, f),
+            HeadlessError::Persistence(error) => Display::fmt(error, f),
+            HeadlessError::Integration(age) => write!(
+                f,
+                "The numerical method could not keep within its tolerances beyond t = {} s, so \
+                 the run stopped there. Try a shorter --dt or some --softening.",
+                age.0
+            ),
+        }
+    }
+}
+
+impl Error for HeadlessError {}
+
+impl From<std::io::Error> for HeadlessError {
+    fn from(error: std::io::Error) -> Self {
+        HeadlessError::Io(error)
+    }
+}
+
+impl From<PersistenceError> for HeadlessError {
+    fn from(error: PersistenceError) -> Self {
+        HeadlessError::Persistence(error)
+    }
+}
+
+fn main() {
+    if let Err(error) = parse_options(std::env::args().skip(1)).and_then(|o| run(&o)) {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
+}
+
+fn parse_options(args: impl Iterator<Item = String>) -> Result<Options, HeadlessError> {
+    let mut options = Options {
+        scenario: ScenarioChoice::PlutoAndCharon,
+        bodies: 300,
+        seed: None,
+        total_mass: Mass(1e24),
+        scale_radius: 1e8,
+        density: 1400.0,
+        duration: TemporalDuration(Scalar::NAN),
+        dt: TemporalDuration(Scalar::NAN),
+        integrator: IntegratorChoice::Leapfrog,
+        position_tolerance: 1.0,
+        velocity_tolerance: 1e-3,
+        relative_tolerance: 1e-6,
+        solver: SolverChoice::Direct,
+        opening_angle: 0.5,
+        collisions: CollisionChoice::Merge,
+        restitution: 1.0,
+        softening: None,
+        snapshot_every: 1,
+        output: PathBuf::from("."),
+        save: None,
+        collision_log: None,
+        checkpoint: None,
+        checkpoint_every: 1000,
+        resume: None,
+    };
+
+    let mut args = args;
+
+    while let Some(flag) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| HeadlessError::Usage(format!("Missing value for {}", flag)))
+        };
+
+        match flag.as_str() {
+            "--scenario" => {
+                options.scenario = match value()?.as_str() {
+                    "pluto-and-charon" => ScenarioChoice::PlutoAndCharon,
+                    "random" => ScenarioChoice::Random,
+                    "plummer" => ScenarioChoice::Plummer,
+                    other => return Err(unknown("scenario", other)),
+                }
+            }
+            "--load" => options.scenario = ScenarioChoice::File(PathBuf::from(value()?)),
+            "--bodies" => options.bodies = parse_number(&flag, &value()?)?,
+            "--seed" => options.seed = Some(parse_number(&flag, &value()?)?),
+            "--total-mass" => options.total_mass = Mass(parse_number(&flag, &value()?)?),
+            "--scale-radius" => options.scale_radius = parse_number(&flag, &value()?)?,
+            "--density" => options.density = parse_number(&flag, &value()?)?,
+            "--duration" => options.duration = TemporalDuration(parse_number(&flag, &value()?)?),
+            "--dt" => options.dt = TemporalDuration(parse_number(&flag, &value()?)?),
+            "--integrator" => {
+                options.integrator = match value()?.as_str() {
+                    "euler" => IntegratorChoice::Euler,
+                    "leapfrog" => IntegratorChoice::Leapfrog,
+                    "rk4" => IntegratorChoice::RungeKutta4,
+                    "dormand-prince" => IntegratorChoice::DormandPrince,
+                    other => return Err(unknown("integrator", other)),
+                }
+            }
+            "--position-tolerance" => options.position_tolerance = parse_number(&flag, &value()?)?,
+            "--velocity-tolerance" => options.velocity_tolerance = parse_number(&flag, &value()?)?,
+            "--relative-tolerance" => options.relative_tolerance = parse_number(&flag, &value()?)?,
+            "--solver" => {
+                options.solver = match value()?.as_str() {
+                    "direct" => SolverChoice::Direct,
+                    "barnes-hut" => SolverChoice::BarnesHut,
+                    other => return Err(unknown("solver", other)),
+                }
+            }
+            "--opening-angle" => options.opening_angle = parse_number(&flag, &value()?)?,
+            "--collisions" => {
+                options.collisions = match value()?.as_str() {
+                    "merge" => CollisionChoice::Merge,
+                    "bounce" => CollisionChoice::Bounce,
+                    "pass-through" => CollisionChoice::PassThrough,
+                    other => return Err(unknown("collision policy", other)),
+                }
+            }
+            "--restitution" => options.restitution = parse_number(&flag, &value()?)?,
+            "--softening" => {
+                options.softening = Some(Softening::Plummer {
+                    length: parse_number(&flag, &value()?)?,
+                })
+            }
+            "--snapshot-every" => options.snapshot_every = parse_number(&flag, &value()?)?,
+            "--output" => options.output = PathBuf::from(value()?),
+            "--save" => options.save = Some(PathBuf::from(value()?)),
+            "--collision-log" => {
+                let path = PathBuf::from(value()?);
+                collision_log_format(&path)?;
+                options.collision_log = Some(path);
+            }
+            "--checkpoint" => options.checkpoint = Some(PathBuf::from(value()?)),
+            "--checkpoint-every" => options.checkpoint_every = parse_number(&flag, &value()?)?,
+            "--resume" => options.resume = Some(PathBuf::from(value()?)),
+            "--help" => return Err(HeadlessError::Usage(String::new())),
+            other => return Err(HeadlessError::Usage(format!("Unknown option {}", other))),
+        }
+    }
+
+    let (duration, dt) = (options.duration.0, options.dt.0);
+    if duration.is_nan() || duration < 0.0 {
+        return Err(HeadlessError::Usage(
+            "A non-negative --duration is required".to_string(),
+        ));
+    }
+
+    // A resumed run keeps the dt it was started with
+    if options.resume.is_none() && (dt.is_nan() || dt <= 0.0) {
+        return Err(HeadlessError::Usage(
+            "A positive --dt is required".to_string(),
+        ));
+    }
+
+    if options.snapshot_every == 0 || options.checkpoint_every == 0 {
+        return Err(HeadlessError::Usage(
+            "--snapshot-every and --checkpoint-every must be at least 1".to_string(),
+        ));
+    }
+
+    Ok(options)
+}
+
+fn parse_number<N: FromStr>(flag: &str, value: &str) -> Result<N, HeadlessError> {
+    value
+        .parse()
+        .map_err(|_| HeadlessError::Usage(format!("Invalid value {} for {}", value, flag)))
+}
+
+fn unknown(kind: &str, value: &str) -> HeadlessError {
+    HeadlessError::Usage(format!("Unknown {} {}", kind, value))
+}
+
+fn numerical_method(options: &Options) -> AnyNumericalMethod {
+    match options.integrator {
+        IntegratorChoice::Euler => AnyNumericalMethod::Euler(EulerMethod),
+        IntegratorChoice::Leapfrog => AnyNumericalMethod::Leapfrog(Leapfrog),
+        IntegratorChoice::RungeKutta4 => AnyNumericalMethod::RungeKutta4(RungeKutta4),
+        IntegratorChoice::DormandPrince => AnyNumericalMethod::DormandPrince(DormandPrince {
+            position_tolerance: options.position_tolerance,
+            velocity_tolerance: options.velocity_tolerance,
+            relative_tolerance: options.relative_tolerance,
+        }),
+    }
+}
+
+fn collision_policy(options: &Options) -> AnyCollisionPolicy {
+    match options.collisions {
+        CollisionChoice::Merge => AnyCollisionPolicy::Merge(Merge),
+        CollisionChoice::Bounce => AnyCollisionPolicy::Bounce(Bounce {
+            restitution: options.restitution,
+        }),
+        CollisionChoice::PassThrough => AnyCollisionPolicy::PassThrough(PassThrough),
+    }
+}
+
+fn force_solver(options: &Options) -> AnyForceSolver {
+    match options.solver {
+        SolverChoice::Direct => AnyForceSolver::DirectSummation(DirectSummation),
+        SolverChoice::BarnesHut => AnyForceSolver::BarnesHut(BarnesHut {
+            opening_angle: options.opening_angle,
+        }),
+    }
+}
+
+fn initial_universe(
+    options: &Options,
+    seed: u64,
+    rng: &mut ChaCha8Rng,
+) -> Result<Universe, PersistenceError> {
+    let universe = match &options.scenario {
+        ScenarioChoice::PlutoAndCharon => universes::pluto_and_charon(),
+        ScenarioChoice::Random => Universe {
+            seed: Some(seed),
+            ..universes::random(
+                rng,
+                options.bodies,
+                Uniform::new(0.0, 1e21),
+                BoxedVector2DDistribution {
+                    x_min: -1e8,
+                    x_max: 1e8,
+                    y_min: -1e8,
+                    y_max: 1e8,
+                },
+                CircularVector2DDistribution {
+                    magnitude_min: 0.0,
+                    magnitude_max: 5e2,
+                },
+                options.density,
+            )
+        },
+        ScenarioChoice::Plummer => Universe {
+            seed: Some(seed),
+            ..universes::plummer_sphere(
+                rng,
+                Gravity {
+                    softening: options.softening.unwrap_or(Gravity::UNIVERSAL.softening),
+                    ..Gravity::UNIVERSAL
+                },
+                options.bodies,
+                options.total_mass,
+                options.scale_radius,
+                options.density,
+            )
+        },
+        ScenarioChoice::File(path) => persistence::load_universe(path)?,
+    };
+
+    Ok(Universe {
+        gravity: Gravity {
+            softening: options.softening.unwrap_or(universe.gravity.softening),
+            ..universe.gravity
+        },
+        ..universe
+    })
+}
+
+fn initial_checkpoint(options: &Options) -> Result<Checkpoint, PersistenceError> {
+    if let Some(path) = &options.resume {
+        return persistence::load_checkpoint(path);
+    }
+
+    let seed = options.seed.unwrap_or_else(rand::random);
+    let mut rng = universes::seeded_rng(seed);
+    let universe = initial_universe(options, seed, &mut rng)?;
+
+    if let Some(seed) = universe.seed {
+        eprintln!("Seed: {}", seed);
+    }
+
+    Ok(Checkpoint {
+        initial_energy: universe.total_energy(),
+        universe,
+        numerical_method: numerical_method(options),
+        force_solver: force_solver(options),
+        collision_policy: collision_policy(options),
+        dt: options.dt,
+        sub_steps: SubSteps::default(),
+        rng,
+        output_lengths: BTreeMap::new(),
+    })
+}
+
+enum CollisionLogFormat {
+    Csv,
+    Jsonl,
+}
+
+fn collision_log_format(path: &Path) -> Result<CollisionLogFormat, HeadlessError> {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("csv") => Ok(CollisionLogFormat::Csv),
+        Some("jsonl") => Ok(CollisionLogFormat::Jsonl),
+        _ => Err(HeadlessError::Usage(
+            "--collision-log must be a .csv or .jsonl file".to_string(),
+        )),
+    }
+}
+
+/// Opens a file to write output to, writing its header unless a resumed run is carrying on with
+/// it. A resumed run first drops whatever was written to it after the checkpoint.
+fn open_output(
+    options: &Options,
+    checkpoint: &Checkpoint,
+    name: &str,
+    path: &Path,
+    header: Option<&str>,
+) -> std::io::Result<BufWriter<File>> {
+    if options.resume.is_some() && path.exists() {
+        let mut file = OpenOptions::new().write(true).open(path)?;
+        if let Some(length) = checkpoint.output_lengths.get(name) {
+            file.set_len(*length)?;
+        }
+        file.seek(SeekFrom::End(0))?;
+        return Ok(BufWriter::new(file));
+    }
+
+    let mut file = BufWriter::new(File::create(path)?);
+    if let Some(header) = header {
+        writeln!(file, "{}", header)?;
+    }
+    Ok(file)
+}
+
+fn open_csv(
+    options: &Options,
+    checkpoint: &Checkpoint,
+    name: &str,
+    header: &str,
+) -> std::io::Result<BufWriter<File>> {
+    open_output(
+        options,
+        checkpoint,
+        name,
+        &options.output.join(name),
+        Some(header),
+    )
+}
+
+/// Writes out everything buffered for `output` and records how long it now is in `checkpoint`.
+fn record_length(
+    checkpoint: &mut Checkpoint,
+    name: &str,
+    output: &mut BufWriter<File>,
+) -> std::io::Result<()> {
+    output.flush()?;
+    let length = output.get_ref().metadata()?.len();
+    checkpoint.output_lengths.insert(name.to_string(), length);
+    Ok(())
+}
+
+struct CollisionLog {
+    format: CollisionLogFormat,
+    output: BufWriter<File>,
+}
+
+impl CollisionLog {
+    const NAME: &'static str = "collision-log";
+
+    fn open(
+        options: &Options,
+        checkpoint: &Checkpoint,
+        path: &Path,
+    ) -> Result<CollisionLog, HeadlessError> {
+        let format = collision_log_format(path)?;
+        let header = match format {
+            CollisionLogFormat::Csv => {
+                Some("age,collision,role,id,mass,x,y,vx,vy,radius,impact_speed")
+            }
+            CollisionLogFormat::Jsonl => None,
+        };
+
+        Ok(CollisionLog {
+            format,
+            output: open_output(options, checkpoint, CollisionLog::NAME, path, header)?,
+        })
+    }
+
+    /// Writes the collisions from one step. In CSV each participant and resulting body gets a
+    /// row, and rows from the same collision share its age and its number within the step.
+    fn write(self: &mut CollisionLog, collisions: &[CollisionEvent]) -> std::io::Result<()> {
+        for (number, collision) in collisions.iter().enumerate() {
+            match self.format {
+                CollisionLogFormat::Csv => {
+                    let participants = collision
+                        .participants
+                        .iter()
+                        .map(|body| ("participant", body));
+                    let results = collision.result.iter().map(|body| ("result", body));
+
+                    for (role, body) in participants.chain(results) {
+                        writeln!(
+                            self.output,
+                            "{},{},{},{},{},{},{},{},{},{},{}",
+                            collision.age.0,
+                            number,
+                            role,
+                            body.id,
+                            body.mass.0,
+                            body.position.0.x,
+                            body.position.0.y,
+                            body.velocity.0.x,
+                            body.velocity.0.y,
+                            body.radius,
+                            collision.impact_speed
+                        )?;
+                    }
+                }
+                CollisionLogFormat::Jsonl => {
+                    writeln!(self.output, "{}", serde_json::to_string(collision)?)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn run(options: &Options) -> Result<(), HeadlessError> {
+    let mut checkpoint = initial_checkpoint(options)?;
+    let engine = checkpoint.engine();
+
+    let dt = checkpoint.dt;
+    let first_step = (checkpoint.universe.age.0 / dt.0).round() as u64;
+    let steps = whole_steps(options.duration, dt)?;
+
+    create_dir_all(&options.output)?;
+
+    let mut snapshots = open_csv(
+        options,
+        &checkpoint,
+        SNAPSHOTS,
+        "age,id,mass,x,y,vx,vy,radius",
+    )?;
+    let mut metrics = open_csv(
+        options,
+        &checkpoint,
+        METRICS,
+        "age,n,momentum_x,momentum_y,angular_momentum,kinetic_energy,potential_energy,\
+         total_energy,energy_drift,virial_ratio,accepted_sub_steps,rejected_sub_steps",
+    )?;
+
+    let mut collision_log = match &options.collision_log {
+        Some(path) => Some(CollisionLog::open(options, &checkpoint, path)?),
+        None => None,
+    };
+
+    for step in first_step..=steps.max(first_step) {
+        // A resumed run wrote its first step before it stopped
+        let resumed_here = options.resume.is_some() && step == first_step;
+
+        if !resumed_here && (step % options.snapshot_every == 0 || step == steps) {
+            write_snapshot(&mut snapshots, &checkpoint.universe)?;
+            write_metrics(
+                &mut metrics,
+                &checkpoint.universe,
+                checkpoint.initial_energy,
+                &checkpoint.sub_steps,
+            )?;
+        }
+
+        if let Some(path) = &options.checkpoint {
+            if !resumed_here && (step % options.checkpoint_every == 0 || step >= steps) {
+                record_length(&mut checkpoint, SNAPSHOTS, &mut snapshots)?;
+                record_length(&mut checkpoint, METRICS, &mut metrics)?;
+                if let Some(log) = &mut collision_log {
+                    record_length(&mut checkpoint, CollisionLog::NAME, &mut log.output)?;
+                }
+                persistence::save_checkpoint(path, &checkpoint)?;
+            }
+        }
+
+        if step < steps {
+            let (new_universe, report) =
+                engine.step_forward_after(&checkpoint.universe, dt, &checkpoint.sub_steps);
+            if report.sub_steps.failed > 0 {
+                return Err(HeadlessError::Integration(new_universe.age));
+            }
+
+            checkpoint.universe = new_universe;
+            checkpoint.sub_steps = report.sub_steps;
+
+            if let Some(log) = &mut collision_log {
+                log.write(&report.collisions)?;
+            }
+        }
+    }
+
+    snapshots.flush()?;
+    metrics.flush()?;
+    if let Some(log) = &mut collision_log {
+        log.output.flush()?;
+    }
+
+    if let Some(path) = &options.save {
+        persistence::save_universe(path, &checkpoint.universe)?;
+    }
+
+    Ok(())
+}
+
+/// How many steps of `dt` make up `duration`, which must be a whole number of them so that the run
+/// ends exactly when asked.
+fn whole_steps(duration: TemporalDuration, dt: TemporalDuration) -> Result<u64, HeadlessError> {
+    let steps = duration.0 / dt.0;
+
+    // Allows for rounding in durations such as 0.3 s of 0.1 s steps
+    if (steps - steps.round()).abs() > 1e-9 * steps.max(1.0) {
+        return Err(HeadlessError::Usage(format!(
+            "--duration must be a whole number of {} s steps",
+            dt.0
+        )));
+    }
+
+    Ok(steps.round() as u64)
+}
+
+fn write_snapshot(output: &mut impl Write, universe: &Universe) -> std::io::Result<()> {
+    for body in &universe.bodies {
+        writeln!(
+            output,
+            "{},{},{},{},{},{},{},{}",
+            universe.age.0,
+            body.id,
+            body.mass.0,
+            body.position.0.x,
+            body.position.0.y,
+            body.velocity.0.x,
+            body.velocity.0.y,
+            body.radius
+        )?;
+    }
+
+    Ok(())
+}
+
+fn write_metrics(
+    output: &mut impl Write,
+    universe: &Universe,
+    initial_energy: Energy,
+    sub_steps: &SubSteps,
+) -> std::io::Result<()> {
+    let momentum = universe.momentum().0;
+
+    writeln!(
+        output,
+        "{},{},{},{},{},{},{},{},{},{},{},{}",
+        universe.age.0,
+        universe.bodies.len(),
+        momentum.x,
+        momentum.y,
+        universe.angular_momentum().0,
+        universe.kinetic_energy().0,
+        universe.potential_energy().0,
+        universe.total_energy().0,
+        universe.energy_drift(initial_energy),
+        universe.virial_ratio(),
+        sub_steps.accepted,
+        sub_steps.rejected
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs::{read_to_string, remove_dir_all};
+    use std::path::{Path, PathBuf};
+
+    use n_body_rust::TemporalDuration;
+
+    use crate::{parse_options, run, whole_steps};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("n-body-rust-{}-{}", std::process::id(), name))
+    }
+
+    fn run_with(output: &Path, steps: u32, extra: &[&str]) {
+        let duration = (steps * 6000).to_string();
+        let log = output.join("collisions.csv");
+        let args = [
+            "--scenario",
+            "random",
+            "--bodies",
+            "100",
+            "--seed",
+            "7",
+            "--dt",
+            "6000",
+            "--duration",
+            &duration,
+            "--output",
+            output.to_str().unwrap(),
+            "--collision-log",
+            log.to_str().unwrap(),
+        ];
+
+        let options = parse_options(args.iter().chain(extra).map(|arg| arg.to_string())).unwrap();
+        run(&options).unwrap();
+    }
+
+    #[test]
+    fn test_resuming_after_a_crash_matches_an_uninterrupted_run() {
+        let uninterrupted = temp_dir("uninterrupted");
+        run_with(&uninterrupted, 40, &[]);
+
+        let resumed = temp_dir("resumed");
+        let checkpoint = temp_dir("resumed-checkpoint.json");
+        let checkpoint = checkpoint.to_str().unwrap();
+        run_with(&resumed, 20, &["--checkpoint", checkpoint]);
+        // The run carried on past its checkpoint before dying, leaving extra rows behind
+        run_with(&resumed, 27, &[]);
+        run_with(&resumed, 40, &["--resume", checkpoint]);
+
+        for name in ["snapshots.csv", "metrics.csv", "collisions.csv"] {
+            assert_eq!(
+                read_to_string(resumed.join(name)).unwrap(),
+                read_to_string(uninterrupted.join(name)).unwrap(),
+                "{}",
+                name
+            );
+        }
+        assert!(read_to_string(uninterrupted.join("collisions.csv"))
+            .unwrap()
+            .contains("participant"));
+
+        remove_dir_all(&uninterrupted).unwrap();
+        remove_dir_all(&resumed).unwrap();
+        std::fs::remove_file(checkpoint).unwrap();
+    }
+
+    #[test]
+    fn test_duration_must_be_a_whole_number_of_steps() {
+        let steps =
+            |duration, dt| whole_steps(TemporalDuration(duration), TemporalDuration(dt)).ok();
+
+        assert_eq!(steps(5.4e6, 600.0), Some(9000));
+        assert_eq!(steps(0.3, 0.1), Some(3));
+        assert_eq!(steps(0.0, 60.0), Some(0));
+        assert_eq!(steps(100.0, 60.0), None);
+        assert_eq!(steps(20.0, 60.0), None);
+    }
+}
@@ -0,0 +1,151 @@
+use n_body_rust::engine::events::successor_of;
+use n_body_rust::{BodyId, CollisionEvent, Position, Scalar, Universe};
+
+use crate::viewport::Viewport;
+
+/// What the camera keeps in view as the universe moves.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum CameraMode {
+    /// Moves along with the centre of mass.
+    CentreOfMass,
+    /// Moves along with a body, and with whatever it merges into.
+    Follow(BodyId),
+    /// Zooms and pans by itself to keep every body in view.
+    FitAll,
+    /// Stays wherever it was last put.
+    Free,
+}
+
+/// The part of the universe shown in the window. Panning and zooming work in every mode, moving
+/// the view relative to whatever it follows, except that they take the camera out of `FitAll`.
+pub struct Camera {
+    pub viewport: Viewport,
+    pub mode: CameraMode,
+    /// Where the thing being followed was at the last update.
+    target: Option<Position>,
+}
+
+impl Camera {
+    pub fn new(viewport: Viewport, mode: CameraMode, universe: &Universe) -> Camera {
+        let mut camera = Camera {
+            viewport,
+            mode: CameraMode::Free,
+            target: None,
+        };
+        camera.set_mode(mode, universe);
+        camera
+    }
+
+    pub fn set_mode(self: &mut Camera, mode: CameraMode, universe: &Universe) {
+        self.mode = mode;
+        self.target = target_of(mode, universe);
+
+        if let Some(target) = self.target {
+            self.viewport = self
+                .viewport
+                .shifted_by((target - self.viewport.centre()).0);
+        }
+        self.fit(universe);
+    }
+
+    /// Moves the view along with whatever it is following, after the universe has stepped forward
+    /// through `collisions`.
+    pub fn update(self: &mut Camera, universe: &Universe, collisions: &[CollisionEvent]) {
+        if let CameraMode::Follow(id) = self.mode {
+            if universe.body(id).is_none() {
+                self.mode = successor_of(id, collisions)
+                    .map(CameraMode::Follow)
+                    .unwrap_or(CameraMode::Free);
+            }
+        }
+
+        let new_target = target_of(self.mode, universe);
+        if let (Some(old_target), Some(new_target)) = (self.target, new_target) {
+            self.viewport = self.viewport.shifted_by((new_target - old_target).0);
+        }
+        self.target = new_target;
+
+        self.fit(universe);
+    }
+
+    /// Zooms by `factor` around the point under `cursor`, in a window of `window_size`.
+    pub fn zoom(self: &mut Camera, window_size: [f64; 2], cursor: [f64; 2], factor: Scalar) {
+        self.take_control();
+
+        let fixed_point = self.viewport.convert_from_window(window_size, cursor);
+        self.viewport = self.viewport.zoomed_around(fixed_point, factor);
+    }
+
+    /// Drags the view by `window_offset`, in a window of `window_size`.
+    pub fn pan(self: &mut Camera, window_size: [f64; 2], window_offset: [f64; 2]) {
+        self.take_control();
+
+        self.viewport = self.viewport.panned_by(window_size, window_offset);
+    }
+
+    fn take_control(self: &mut Camera) {
+        if self.mode == CameraMode::FitAll {
+            self.mode = CameraMode::Free;
+        }
+    }
+
+    fn fit(self: &mut Camera, universe: &Universe) {
+        if self.mode == CameraMode::FitAll {
+            let current_size = self.viewport.x_max - self.viewport.x_min;
+            if let Some(viewport) = Viewport::square_fitting(&universe.bodies, 0.05, current_size) {
+                self.viewport = viewport;
+            }
+        }
+    }
+}
+
+fn target_of(mode: CameraMode, universe: &Universe) -> Option<Position> {
+    match mode {
+        CameraMode::CentreOfMass if !universe.bodies.is_empty() => Some(universe.centre_of_mass()),
+        CameraMode::Follow(id) => universe.body(id).map(|body| body.position),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::camera::{Camera, CameraMode};
+    use crate::viewport::Viewport;
+    use n_body_rust::{
+        universes, Body, BodyId, DirectSummation, Engine, Leapfrog, Mass, Merge, Position,
+        TemporalDuration, Vector2D, Velocity,
+    };
+
+    #[test]
+    fn test_follow_moves_with_the_body_and_on_through_merges() {
+        let mut universe = universes::pluto_and_charon();
+        let charon = universe.bodies[1];
+        let mut camera = Camera::new(
+            Viewport::square_around(Position(Vector2D::zero()), 1e8),
+            CameraMode::Follow(charon.id),
+            &universe,
+        );
+        camera.pan([100.0, 100.0], [10.0, 0.0]);
+        let offset = (camera.viewport.centre() - charon.position).0;
+
+        // Drop a heavier body onto Charon, which it absorbs
+        universe.add_body(Body {
+            id: BodyId::default(),
+            mass: Mass(1e23),
+            velocity: Velocity(Vector2D::zero()),
+            ..charon
+        });
+
+        let engine = Engine {
+            numerical_method: Leapfrog,
+            force_solver: DirectSummation,
+            collision_policy: Merge,
+        };
+        let (universe, report) = engine.step_forward_with_report(&universe, TemporalDuration(1.0));
+        camera.update(&universe, &report.collisions);
+
+        let merged = universe.bodies[1];
+        assert_eq!(camera.mode, CameraMode::Follow(merged.id));
+        assert!(((camera.viewport.centre() - merged.position).0 - offset).magnitude() < 1e-6);
+    }
+}
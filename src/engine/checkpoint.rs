@@ -0,0 +1,150 @@
+use std::collections::BTreeMap;
+
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::engine::universe::Universe;
+use crate::engine::Engine;
+use crate::physics::collision::AnyCollisionPolicy;
+use crate::physics::force_solvers::AnyForceSolver;
+use crate::physics::numerical_methods::{AnyNumericalMethod, SubSteps};
+use crate::physics::primitives::{Energy, TemporalDuration};
+
+/// Everything needed to carry on a run where it stopped. Stepping a restored checkpoint gives
+/// bit-identical results to a run that was never interrupted.
+///
+/// The numerical method, force solver and collision policy are kept as the `Any*` enums, which
+/// record which one the run chose, so that a checkpoint can be restored without knowing it.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub universe: Universe,
+    pub numerical_method: AnyNumericalMethod,
+    pub force_solver: AnyForceSolver,
+    pub collision_policy: AnyCollisionPolicy,
+    pub dt: TemporalDuration,
+    /// What the numerical method reported for the last step, which the next one carries on from.
+    #[serde(default)]
+    pub sub_steps: SubSteps,
+    /// The total energy when the run started, which energy drift is measured against.
+    pub initial_energy: Energy,
+    pub rng: ChaCha8Rng,
+    /// How many bytes each of the run's outputs held when the checkpoint was taken, so that a
+    /// resumed run can drop anything written after it.
+    #[serde(default)]
+    pub output_lengths: BTreeMap<String, u64>,
+}
+
+impl Checkpoint {
+    pub fn engine(
+        self: &Checkpoint,
+    ) -> Engine<AnyNumericalMethod, AnyForceSolver, AnyCollisionPolicy> {
+        Engine {
+            numerical_method: self.numerical_method,
+            force_solver: self.force_solver,
+            collision_policy: self.collision_policy,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use rand::distributions::Uniform;
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::engine::checkpoint::Checkpoint;
+    use crate::engine::universe::Universe;
+    use crate::persistence::{load_checkpoint, save_checkpoint};
+    use crate::physics::collision::{AnyCollisionPolicy, Merge};
+    use crate::physics::force_solvers::{AnyForceSolver, BarnesHut};
+    use crate::physics::gravity::{Gravity, Softening};
+    use crate::physics::numerical_methods::{AnyNumericalMethod, DormandPrince, SubSteps};
+    use crate::physics::primitives::{Scalar, TemporalDuration};
+    use crate::universes;
+    use crate::universes::{BoxedVector2DDistribution, CircularVector2DDistribution};
+
+    fn new_checkpoint() -> Checkpoint {
+        let mut rng = ChaCha8Rng::seed_from_u64(11);
+
+        let universe = Universe {
+            gravity: Gravity {
+                softening: Softening::Plummer { length: 1e6 },
+                ..Gravity::UNIVERSAL
+            },
+            ..universes::random(
+                &mut rng,
+                40,
+                Uniform::new(0.0, 1e21),
+                BoxedVector2DDistribution {
+                    x_min: -1e8,
+                    x_max: 1e8,
+                    y_min: -1e8,
+                    y_max: 1e8,
+                },
+                CircularVector2DDistribution {
+                    magnitude_min: 0.0,
+                    magnitude_max: 5e2,
+                },
+                1400.0,
+            )
+        };
+
+        Checkpoint {
+            initial_energy: universe.total_energy(),
+            universe,
+            numerical_method: AnyNumericalMethod::DormandPrince(DormandPrince {
+                position_tolerance: 1.0,
+                velocity_tolerance: 1e-3,
+                relative_tolerance: 1e-6,
+            }),
+            force_solver: AnyForceSolver::BarnesHut(BarnesHut { opening_angle: 0.5 }),
+            collision_policy: AnyCollisionPolicy::Merge(Merge),
+            dt: TemporalDuration(1e3),
+            sub_steps: SubSteps::default(),
+            rng,
+            output_lengths: BTreeMap::new(),
+        }
+    }
+
+    fn advance(checkpoint: Checkpoint, steps: usize) -> Checkpoint {
+        let engine = checkpoint.engine();
+        let mut checkpoint = checkpoint;
+
+        for _ in 0..steps {
+            let (universe, report) = engine.step_forward_after(
+                &checkpoint.universe,
+                checkpoint.dt,
+                &checkpoint.sub_steps,
+            );
+            checkpoint.universe = universe;
+            checkpoint.sub_steps = report.sub_steps;
+        }
+
+        checkpoint
+    }
+
+    #[test]
+    fn resuming_gives_bit_identical_results() {
+        let initial = new_checkpoint();
+        let uninterrupted = advance(initial.clone(), 40);
+
+        for name in ["checkpoint.json", "checkpoint.ron"] {
+            let path =
+                std::env::temp_dir().join(format!("n-body-rust-{}-{}", std::process::id(), name));
+
+            save_checkpoint(&path, &advance(initial.clone(), 25)).unwrap();
+            let restored = load_checkpoint(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+
+            let mut resumed = advance(restored, 15);
+
+            assert_eq!(resumed, uninterrupted);
+            assert_eq!(
+                resumed.rng.gen::<Scalar>(),
+                uninterrupted.clone().rng.gen::<Scalar>()
+            );
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use serde::{Deserialize, Serialize};
+
+use crate::engine::universe::{Body, BodyId};
+use crate::physics::collision::touching;
+use crate::physics::primitives::{Scalar, TemporalDuration};
+
+/// A record of bodies colliding.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct CollisionEvent {
+    pub age: TemporalDuration,
+    /// The bodies that collided, as they were when they touched.
+    pub participants: Vec<Body>,
+    /// The fastest that any two touching participants were closing on each other along the line
+    /// between their centres, or zero if none were.
+    pub impact_speed: Scalar,
+    /// The bodies that the participants became.
+    pub result: Vec<Body>,
+}
+
+impl CollisionEvent {
+    pub fn new(
+        age: TemporalDuration,
+        participants: Vec<Body>,
+        result: Vec<Body>,
+    ) -> CollisionEvent {
+        let impact_speed = participants
+            .iter()
+            .enumerate()
+            .flat_map(|(index, left)| {
+                participants[index + 1..]
+                    .iter()
+                    .filter(move |right| touching(left, right))
+                    .map(move |right| {
+                        let towards_right = (right.position - left.position).0.unit();
+                        (left.velocity.0 - right.velocity.0).dot(towards_right)
+                    })
+            })
+            .fold(0.0, Scalar::max);
+
+        CollisionEvent {
+            age,
+            participants,
+            impact_speed,
+            result,
+        }
+    }
+}
+
+/// The body that body `id` became in `collisions`, if it became exactly one.
+pub fn successor_of(id: BodyId, collisions: &[CollisionEvent]) -> Option<BodyId> {
+    collisions
+        .iter()
+        .find(|collision| collision.participants.iter().any(|body| body.id == id))
+        .filter(|collision| collision.result.len() == 1)
+        .map(|collision| collision.result[0].id)
+}
@@ -1,12 +1,24 @@
-use std::fmt::Debug;
-
+use crate::engine::universe::BodyId;
 use crate::engine::Universe;
-use crate::physics::primitives::{Scalar, Vector2D};
+use crate::physics::orbit::OrbitalElements;
+use crate::physics::primitives::{Energy, Mass};
 
 pub enum Metric {
     NumBodies,
     Momentum,
+    AngularMomentum,
     KineticEnergy,
+    PotentialEnergy,
+    TotalEnergy,
+    /// Relative drift in total energy since the universe had the given initial energy
+    EnergyDrift(Energy),
+    VirialRatio,
+    /// Osculating semi-major axis of the given body around its primary
+    SemiMajorAxis(BodyId),
+    /// Osculating eccentricity of the given body around its primary
+    Eccentricity(BodyId),
+    /// Osculating orbital period of the given body around its primary
+    OrbitalPeriod(BodyId),
 }
 
 impl Metric {
@@ -14,7 +26,23 @@ impl Metric {
         match self {
             Metric::NumBodies => universe.bodies.len().to_string(),
             Metric::Momentum => universe.momentum().0.to_string(),
+            Metric::AngularMomentum => universe.angular_momentum().0.to_string(),
             Metric::KineticEnergy => universe.kinetic_energy().0.to_string(),
+            Metric::PotentialEnergy => universe.potential_energy().0.to_string(),
+            Metric::TotalEnergy => universe.total_energy().0.to_string(),
+            Metric::EnergyDrift(initial_energy) => {
+                format!("{:e}", universe.energy_drift(*initial_energy))
+            }
+            Metric::VirialRatio => universe.virial_ratio().to_string(),
+            Metric::SemiMajorAxis(id) => orbit_metric(universe, *id, |elements, _| {
+                elements.semi_major_axis.to_string()
+            }),
+            Metric::Eccentricity(id) => orbit_metric(universe, *id, |elements, _| {
+                format!("{:e}", elements.eccentricity)
+            }),
+            Metric::OrbitalPeriod(id) => orbit_metric(universe, *id, |elements, total_mass| {
+                elements.period(&universe.gravity, total_mass).0.to_string()
+            }),
         }
     }
 
@@ -22,7 +50,28 @@ impl Metric {
         match self {
             Metric::NumBodies => "n",
             Metric::Momentum => "Σp̃",
+            Metric::AngularMomentum => "ΣL",
             Metric::KineticEnergy => "ΣEₖ",
+            Metric::PotentialEnergy => "ΣEₚ",
+            Metric::TotalEnergy => "E",
+            Metric::EnergyDrift(_) => "|ΔE/E₀|",
+            Metric::VirialRatio => "2Eₖ/|Eₚ|",
+            Metric::SemiMajorAxis(_) => "a",
+            Metric::Eccentricity(_) => "e",
+            Metric::OrbitalPeriod(_) => "T",
         }
     }
 }
+
+/// Formats an element of a body's orbit, or a dash if it no longer exists or has no bound orbit
+/// to report.
+fn orbit_metric(
+    universe: &Universe,
+    id: BodyId,
+    format: impl Fn(&OrbitalElements, Mass) -> String,
+) -> String {
+    universe
+        .orbit_of(id)
+        .map(|(elements, total_mass)| format(&elements, total_mass))
+        .unwrap_or_else(|| "-".to_string())
+}
@@ -1,85 +1,443 @@
-use std::collections::{HashMap, HashSet};
-
+use crate::engine::events::CollisionEvent;
 use crate::engine::universe::{Body, Universe};
-use crate::physics::collision::collide;
+use crate::physics::collision::{touching_groups, CollisionPolicy};
+use crate::physics::force_solvers::ForceSolver;
 use crate::physics::gravity::Gravity;
-use crate::physics::numerical_methods::{euler_method, OdeAlgorithm};
-use crate::physics::primitives::*;
+use crate::physics::numerical_methods::{OdeAlgorithm, PhaseSpace, SubSteps};
 use crate::physics::primitives::*;
 
+pub mod checkpoint;
+pub mod events;
 pub mod metrics;
 pub mod universe;
 
-pub struct Engine<A: OdeAlgorithm<Vector2D, Scalar>> {
+pub struct Engine<A: OdeAlgorithm<PhaseSpace, Scalar>, S: ForceSolver, C: CollisionPolicy> {
     pub numerical_method: A,
+    pub force_solver: S,
+    pub collision_policy: C,
+}
+
+/// What happened during a step, besides the bodies moving.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct StepReport {
+    /// How many sub-steps the numerical method took.
+    pub sub_steps: SubSteps,
+    pub collisions: Vec<CollisionEvent>,
 }
 
-impl<A: OdeAlgorithm<Vector2D, Scalar>> Engine<A> {
-    pub fn step_forward(self: &Engine<A>, universe: &Universe, dt: TemporalDuration) -> Universe {
-        let mut indexes_of_deleted_bodies: HashSet<usize> = HashSet::new();
+impl<A: OdeAlgorithm<PhaseSpace, Scalar>, S: ForceSolver, C: CollisionPolicy> Engine<A, S, C> {
+    pub fn step_forward(
+        self: &Engine<A, S, C>,
+        universe: &Universe,
+        dt: TemporalDuration,
+    ) -> Universe {
+        self.step_forward_with_report(universe, dt).0
+    }
 
-        let new_bodies = universe
-            .bodies
+    /// As `step_forward`, but also reports the collisions and sub-steps taken along the way.
+    pub fn step_forward_with_report(
+        self: &Engine<A, S, C>,
+        universe: &Universe,
+        dt: TemporalDuration,
+    ) -> (Universe, StepReport) {
+        self.step_forward_after(universe, dt, &SubSteps::default())
+    }
+
+    /// As `step_forward_with_report`, for the step following one that took `previous` sub-steps.
+    /// Adaptive numerical methods carry on from the sub-step length they had settled on, rather
+    /// than finding it again from scratch.
+    pub fn step_forward_after(
+        self: &Engine<A, S, C>,
+        universe: &Universe,
+        dt: TemporalDuration,
+        previous: &SubSteps,
+    ) -> (Universe, StepReport) {
+        let (bodies_after_collisions, collisions) =
+            self.resolve_collisions(&universe.bodies, universe.age);
+
+        let initial_state = PhaseSpace {
+            positions: bodies_after_collisions
+                .iter()
+                .map(|b| b.position.0)
+                .collect(),
+            velocities: bodies_after_collisions
+                .iter()
+                .map(|b| b.velocity.0)
+                .collect(),
+        };
+
+        let (final_state, sub_steps) = self.numerical_method.next_y_with_sub_steps(
+            |_, state| self.derivative(&universe.gravity, &bodies_after_collisions, state),
+            initial_state,
+            universe.age.0,
+            dt.0,
+            previous,
+        );
+
+        let new_bodies = bodies_after_collisions
             .iter()
-            .enumerate()
-            .filter_map(|(object_index, object)| {
-                if indexes_of_deleted_bodies.contains(&object_index) {
-                    return None;
-                }
-
-                // TODO this is incorrect when there is a collision, which edits the position too
-                let new_position = object.position + object.velocity * dt;
-
-                let mut total_force = Force(Vector2D::zero());
-
-                let mut object_after_all_collisions: Body = *object;
-
-                for (subject_index, subject) in universe.bodies.iter().enumerate() {
-                    if std::ptr::eq(subject, object)
-                        || indexes_of_deleted_bodies.contains(&subject_index)
-                    {
-                        continue;
-                    }
-
-                    let collision_result: Option<Body> =
-                        collide(&object_after_all_collisions, subject, dt);
-
-                    if collision_result.is_none() {
-                        total_force = total_force
-                            + universe
-                                .gravity
-                                .due_to_bodies(&object_after_all_collisions, &subject);
-                    } else {
-                        indexes_of_deleted_bodies.insert(subject_index);
-                    }
-
-                    object_after_all_collisions =
-                        collision_result.unwrap_or(object_after_all_collisions);
-                }
-
-                let acceleration = total_force / object_after_all_collisions.mass;
-
-                let new_velocity = Velocity(self.numerical_method.next_y(
-                    |_, _| acceleration.0,
-                    object_after_all_collisions.velocity.0,
-                    universe.age.0,
-                    dt.0,
-                ));
-
-                let new_body = Body {
-                    position: new_position,
-                    velocity: new_velocity,
-                    ..(object_after_all_collisions)
-                };
-
-                Some(new_body)
+            .zip(final_state.positions)
+            .zip(final_state.velocities)
+            .map(|((body, position), velocity)| Body {
+                position: Position(position),
+                velocity: Velocity(velocity),
+                ..(*body)
             })
             .collect();
 
-        Universe {
+        let new_universe = Universe {
             bodies: new_bodies,
-            age: universe.age + dt,
+            // Only as far as the numerical method got, should a sub-step have failed
+            age: TemporalDuration(universe.age.0 + dt.0 - sub_steps.unfinished),
+            names: universe.names.clone(),
             ..(*universe)
+        };
+
+        (
+            new_universe,
+            StepReport {
+                sub_steps,
+                collisions,
+            },
+        )
+    }
+
+    /// The time derivative of the whole system: the velocity of each body alongside the
+    /// gravitational acceleration it feels when every body is at the positions in `state`.
+    ///
+    /// `bodies` supplies the masses, and must be in the same order as `state`.
+    pub fn derivative(
+        self: &Engine<A, S, C>,
+        gravity: &Gravity,
+        bodies: &[Body],
+        state: PhaseSpace,
+    ) -> PhaseSpace {
+        let bodies_in_state: Vec<Body> = bodies
+            .iter()
+            .zip(state.positions.iter())
+            .map(|(body, position)| Body {
+                position: Position(*position),
+                ..(*body)
+            })
+            .collect();
+
+        let accelerations = self
+            .force_solver
+            .forces(gravity, &bodies_in_state)
+            .into_iter()
+            .zip(bodies_in_state.iter())
+            .map(|(force, body)| (force / body.mass).0)
+            .collect();
+
+        PhaseSpace {
+            positions: state.velocities,
+            velocities: accelerations,
+        }
+    }
+
+    /// Finds every group of touching bodies before changing any of them, then resolves each group
+    /// exactly once. Which bodies collide therefore does not depend on their order.
+    ///
+    /// Groups that the collision policy leaves unchanged, such as bodies passing through each
+    /// other, are not reported as collisions.
+    fn resolve_collisions(
+        self: &Engine<A, S, C>,
+        bodies: &[Body],
+        age: TemporalDuration,
+    ) -> (Vec<Body>, Vec<CollisionEvent>) {
+        let mut remaining_bodies = Vec::with_capacity(bodies.len());
+        let mut collisions = Vec::new();
+
+        for group in touching_groups(bodies) {
+            let participants: Vec<Body> = group.iter().map(|index| bodies[*index]).collect();
+
+            if participants.len() == 1 {
+                remaining_bodies.extend(participants);
+                continue;
+            }
+
+            let result = self.collision_policy.resolve_group(&participants);
+
+            if result != participants {
+                collisions.push(CollisionEvent::new(age, participants, result.clone()));
+            }
+
+            remaining_bodies.extend(result);
+        }
+
+        (remaining_bodies, collisions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::universe::{Body, BodyId, Universe};
+    use crate::engine::Engine;
+    use crate::physics::collision::{Bounce, CollisionPolicy, Merge, PassThrough};
+    use crate::physics::force_solvers::DirectSummation;
+    use crate::physics::numerical_methods::{
+        DormandPrince, EulerMethod, Leapfrog, OdeAlgorithm, PhaseSpace,
+    };
+    use crate::physics::primitives::{
+        Mass, Momentum, Position, Scalar, TemporalDuration, Vector2D, Velocity,
+    };
+    use crate::universes;
+
+    fn body(id: u64, mass: Scalar, x: Scalar, velocity: Vector2D) -> Body {
+        Body {
+            id: BodyId(id),
+            mass: Mass(mass),
+            position: Position(Vector2D::new(x, 0.0)),
+            velocity: Velocity(velocity),
+            radius: 1.0,
         }
     }
+
+    /// The first touches the second, which touches the third, but the first and third are apart
+    fn chain_of_three() -> [Body; 3] {
+        [
+            body(0, 1.0, 0.0, Vector2D::new(1.0, 0.0)),
+            body(1, 2.0, 1.5, Vector2D::new(0.0, 2.0)),
+            body(2, 1.0, 3.0, Vector2D::new(-1.0, 0.0)),
+        ]
+    }
+
+    fn engine_with<C: CollisionPolicy>(
+        collision_policy: C,
+    ) -> Engine<Leapfrog, DirectSummation, C> {
+        Engine {
+            numerical_method: Leapfrog,
+            force_solver: DirectSummation,
+            collision_policy,
+        }
+    }
+
+    fn total_momentum(bodies: &[Body]) -> Momentum {
+        bodies.iter().fold(Momentum(Vector2D::zero()), |acc, body| {
+            acc + body.momentum()
+        })
+    }
+
+    #[test]
+    fn three_way_collision_merges_once_whatever_the_order() {
+        let chain = chain_of_three();
+        let bystander = body(3, 5.0, 100.0, Vector2D::new(0.0, -3.0));
+        let expected = Body {
+            id: BodyId(1),
+            mass: Mass(4.0),
+            position: Position(Vector2D::new(1.5, 0.0)),
+            velocity: Velocity(Vector2D::new(0.0, 1.0)),
+            radius: Scalar::cbrt(3.0),
+        };
+
+        let orders = [
+            [0, 1, 2],
+            [0, 2, 1],
+            [1, 0, 2],
+            [1, 2, 0],
+            [2, 0, 1],
+            [2, 1, 0],
+        ];
+
+        for order in orders {
+            let mut bodies: Vec<Body> = order.iter().map(|index| chain[*index]).collect();
+            bodies.insert(1, bystander);
+
+            let resolved = engine_with(Merge)
+                .resolve_collisions(&bodies, TemporalDuration(0.0))
+                .0;
+
+            assert_eq!(resolved.len(), 2, "order {:?}", order);
+            assert!(resolved.contains(&expected), "order {:?}", order);
+            assert!(resolved.contains(&bystander), "order {:?}", order);
+            assert_eq!(total_momentum(&resolved).0, total_momentum(&bodies).0);
+        }
+    }
+
+    #[test]
+    fn separate_groups_merge_separately() {
+        let bodies = [
+            body(0, 1.0, 0.0, Vector2D::new(1.0, 0.0)),
+            body(1, 3.0, 50.0, Vector2D::new(0.0, 2.0)),
+            body(2, 1.0, 1.0, Vector2D::new(-1.0, 0.0)),
+            body(3, 1.0, 51.0, Vector2D::new(2.0, 0.0)),
+        ];
+
+        let resolved = engine_with(Merge)
+            .resolve_collisions(&bodies, TemporalDuration(0.0))
+            .0;
+
+        assert_eq!(
+            resolved,
+            vec![
+                body(0, 2.0, 0.5, Vector2D::new(0.0, 0.0)),
+                body(1, 4.0, 50.25, Vector2D::new(0.5, 1.5)),
+            ]
+            .into_iter()
+            .map(|merged| Body {
+                radius: Scalar::cbrt(2.0),
+                ..merged
+            })
+            .collect::<Vec<Body>>()
+        );
+    }
+
+    #[test]
+    fn collisions_are_reported() {
+        let mut universe = universes::pluto_and_charon();
+        universe.bodies = chain_of_three().to_vec();
+        universe
+            .bodies
+            .insert(1, body(3, 5.0, 100.0, Vector2D::new(0.0, -3.0)));
+        universe.age = TemporalDuration(60.0);
+
+        let (_, report) =
+            engine_with(Merge).step_forward_with_report(&universe, TemporalDuration(1.0));
+
+        assert_eq!(report.collisions.len(), 1);
+        let collision = &report.collisions[0];
+        assert_eq!(collision.age, TemporalDuration(60.0));
+        assert_eq!(
+            collision.participants,
+            vec![universe.bodies[0], universe.bodies[2], universe.bodies[3]]
+        );
+        assert_eq!(collision.result.len(), 1);
+        assert_eq!(collision.result[0].id, BodyId(1));
+        assert_eq!(collision.result[0].mass, Mass(4.0));
+        // Only the closing speed along the line between the touching bodies counts
+        assert_eq!(collision.impact_speed, 1.0);
+
+        let (_, report) =
+            engine_with(PassThrough).step_forward_with_report(&universe, TemporalDuration(1.0));
+        assert!(report.collisions.is_empty());
+    }
+
+    #[test]
+    fn ids_survive_steps_and_are_never_reused() {
+        let mut universe = Universe::new(
+            universes::pluto_and_charon().gravity,
+            chain_of_three().to_vec(),
+        );
+        universe.name_body(BodyId(2), "Absorbed");
+
+        let mut universe = engine_with(Merge).step_forward(&universe, TemporalDuration(1.0));
+        assert_eq!(universe.bodies.len(), 1);
+        assert_eq!(universe.bodies[0].id, BodyId(1));
+        assert_eq!(universe.name_of(BodyId(2)), Some("Absorbed"));
+
+        let newcomer = universe.add_body(body(0, 1.0, 1e9, Vector2D::zero()));
+        assert_eq!(newcomer, BodyId(3));
+
+        let universe = engine_with(Merge).step_forward(&universe, TemporalDuration(1.0));
+        let ids: Vec<BodyId> = universe.bodies.iter().map(|body| body.id).collect();
+        assert_eq!(ids, vec![BodyId(1), BodyId(3)]);
+    }
+
+    #[test]
+    fn three_way_bounce_conserves_momentum_whatever_the_order() {
+        let chain = chain_of_three();
+        let engine = engine_with(Bounce { restitution: 1.0 });
+        let expected = engine.resolve_collisions(&chain, TemporalDuration(0.0)).0;
+
+        let orders = [
+            [0, 1, 2],
+            [0, 2, 1],
+            [1, 0, 2],
+            [1, 2, 0],
+            [2, 0, 1],
+            [2, 1, 0],
+        ];
+
+        for order in orders {
+            let bodies: Vec<Body> = order.iter().map(|index| chain[*index]).collect();
+
+            let resolved = engine.resolve_collisions(&bodies, TemporalDuration(0.0)).0;
+
+            assert_eq!(resolved.len(), 3, "order {:?}", order);
+            for body in &expected {
+                assert!(resolved.contains(body), "order {:?}", order);
+            }
+            let (before, after) = (total_momentum(&bodies).0, total_momentum(&resolved).0);
+            assert!(
+                (after - before).magnitude() < 1e-12,
+                "{} became {}",
+                before,
+                after
+            );
+        }
+    }
+
+    /// Runs `pluto_and_charon` for roughly ten orbits, returning the worst relative energy error
+    fn max_energy_error<A: OdeAlgorithm<PhaseSpace, Scalar>>(numerical_method: A) -> Scalar {
+        let engine = Engine {
+            numerical_method,
+            force_solver: DirectSummation,
+            collision_policy: Merge,
+        };
+
+        let mut universe = universes::pluto_and_charon();
+        let initial_energy = universe.total_energy();
+        let mut max_error: Scalar = 0.0;
+
+        for _ in 0..10_000 {
+            universe = engine.step_forward(&universe, TemporalDuration(600.0));
+            max_error = max_error.max(universe.energy_drift(initial_energy));
+        }
+
+        max_error
+    }
+
+    #[test]
+    fn reversed_leapfrog_retraces_its_path() {
+        let engine = engine_with(Merge);
+        let initial = universes::pluto_and_charon();
+
+        let mut universe = initial.clone();
+        for _ in 0..1_000 {
+            universe = engine.step_forward(&universe, TemporalDuration(600.0));
+        }
+        for _ in 0..1_000 {
+            universe = engine.step_forward(&universe, TemporalDuration(-600.0));
+        }
+
+        assert!(engine.numerical_method.is_time_reversible());
+        assert!(universe.age.0.abs() < 1e-6);
+        for (body, initial_body) in universe.bodies.iter().zip(initial.bodies.iter()) {
+            let error = (body.position - initial_body.position).0.magnitude();
+            assert!(error < 1e-3, "{} m from where it started", error);
+        }
+    }
+
+    #[test]
+    fn leapfrog_energy_error_is_bounded() {
+        let leapfrog_error = max_energy_error(Leapfrog);
+        let euler_error = max_energy_error(EulerMethod);
+
+        assert!(leapfrog_error < 1e-4, "leapfrog error {}", leapfrog_error);
+        assert!(
+            euler_error > 100.0 * leapfrog_error,
+            "euler error {}",
+            euler_error
+        );
+    }
+
+    #[test]
+    fn a_failed_step_only_ages_the_universe_as_far_as_it_got() {
+        let engine = Engine {
+            numerical_method: DormandPrince {
+                position_tolerance: 1e-30,
+                velocity_tolerance: 1e-30,
+                relative_tolerance: 1e-30,
+            },
+            force_solver: DirectSummation,
+            collision_policy: Merge,
+        };
+        let universe = universes::pluto_and_charon();
+
+        let (stepped, report) = engine.step_forward_with_report(&universe, TemporalDuration(1e5));
+
+        assert_eq!(report.sub_steps.failed, 1);
+        assert_eq!(stepped.bodies, universe.bodies);
+        assert_eq!(stepped.age, universe.age);
+    }
 }
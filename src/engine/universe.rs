@@ -1,24 +1,77 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
 use crate::physics::gravity::*;
+use crate::physics::orbit::OrbitalElements;
 use crate::physics::primitives::*;
 
+/// Identifies a body for as long as it exists, however the bodies around it come and go. Ids are
+/// unique within a universe and are never reused.
+#[derive(
+    Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone, Default, Serialize, Deserialize,
+)]
+pub struct BodyId(pub u64);
+
+impl BodyId {
+    /// Stands in for the id of a body loaded from a file that did not give it one, until the
+    /// universe assigns it a real one.
+    const MISSING: BodyId = BodyId(u64::MAX);
+
+    fn missing() -> BodyId {
+        BodyId::MISSING
+    }
+}
+
+impl Display for BodyId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 // TODO do we need copy/clone here?
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
 pub struct Body {
+    /// Assigned by the universe when the body is added to it.
+    #[serde(default = "BodyId::missing")]
+    pub id: BodyId,
     pub mass: Mass,
     pub position: Position,
     pub velocity: Velocity,
+    /// Bodies collide when their surfaces touch. A point mass has a radius of zero.
+    #[serde(default)]
+    pub radius: Scalar,
 }
 
 impl Body {
+    /// The radius of a sphere of `mass` with uniform `density`, in kg/m³.
+    pub fn radius_for_density(mass: Mass, density: Scalar) -> Scalar {
+        (3.0 * mass.0 / (4.0 * std::f64::consts::PI * density)).cbrt()
+    }
+
     pub fn momentum(&self) -> Momentum {
         self.mass * self.velocity
     }
 }
 
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct Universe {
     pub gravity: Gravity,
     pub bodies: Vec<Body>,
     pub age: TemporalDuration,
+    /// The seed this universe was randomly generated from, so that it can be generated again.
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// Names for some of the bodies. Names are kept here rather than on each body so that bodies
+    /// stay cheap to copy, and outlive bodies that merge away so that records can still name them.
+    /// Each step shares them with the universe before it rather than copying them.
+    #[serde(default)]
+    pub names: Arc<BTreeMap<BodyId, String>>,
+    /// The id that the next body added will be given.
+    #[serde(default)]
+    pub next_body_id: BodyId,
 }
 
 impl Gravity {
@@ -28,8 +81,90 @@ impl Gravity {
 }
 
 impl Universe {
-    pub fn add_body(&mut self, body: Body) -> () {
-        self.bodies.push(body);
+    /// A universe just beginning, holding `bodies` numbered in order from zero.
+    pub fn new(gravity: Gravity, bodies: Vec<Body>) -> Universe {
+        let mut universe = Universe {
+            gravity,
+            bodies: Vec::with_capacity(bodies.len()),
+            age: TemporalDuration(0.0),
+            seed: None,
+            names: Arc::new(BTreeMap::new()),
+            next_body_id: BodyId(0),
+        };
+
+        for body in bodies {
+            universe.add_body(body);
+        }
+
+        universe
+    }
+
+    /// Adds `body` under a new id, replacing any id it had, and returns that id.
+    pub fn add_body(&mut self, body: Body) -> BodyId {
+        let id = self.next_body_id;
+        self.next_body_id = BodyId(id.0 + 1);
+
+        self.bodies.push(Body { id, ..body });
+        id
+    }
+
+    /// Takes body `id` out of the universe, returning it if it was there. Its id is not reused.
+    pub fn remove_body(&mut self, id: BodyId) -> Option<Body> {
+        let index = self.bodies.iter().position(|body| body.id == id)?;
+
+        Some(self.bodies.remove(index))
+    }
+
+    /// Gives each body loaded without an id a new one, after every id already in use, and makes
+    /// sure the next body added gets a new one too. Universes saved before bodies had ids load
+    /// with every id missing, and so are numbered in order. Fails with the id of any two bodies
+    /// that share one.
+    pub fn assign_missing_ids(&mut self) -> Result<(), BodyId> {
+        let mut ids = BTreeSet::new();
+        for body in &self.bodies {
+            if body.id != BodyId::MISSING && !ids.insert(body.id) {
+                return Err(body.id);
+            }
+        }
+
+        let after_last_id = ids.iter().next_back().map_or(0, |id| id.0 + 1);
+        self.next_body_id = BodyId(self.next_body_id.0.max(after_last_id));
+
+        for body in &mut self.bodies {
+            if body.id == BodyId::MISSING {
+                body.id = self.next_body_id;
+                self.next_body_id = BodyId(self.next_body_id.0 + 1);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn body(self: &Universe, id: BodyId) -> Option<&Body> {
+        self.bodies.iter().find(|body| body.id == id)
+    }
+
+    /// Names body `id`, replacing any name it had.
+    pub fn name_body(&mut self, id: BodyId, name: &str) {
+        Arc::make_mut(&mut self.names).insert(id, name.to_string());
+    }
+
+    pub fn name_of(self: &Universe, id: BodyId) -> Option<&str> {
+        self.names.get(&id).map(String::as_str)
+    }
+
+    pub fn id_named(self: &Universe, name: &str) -> Option<BodyId> {
+        self.names
+            .iter()
+            .find(|(_, body_name)| body_name.as_str() == name)
+            .map(|(id, _)| *id)
+    }
+
+    /// The name of the body, or its id if it has no name.
+    pub fn label_of(self: &Universe, id: BodyId) -> String {
+        self.name_of(id)
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("#{}", id))
     }
 
     // TODO the following should really be in the physics package
@@ -56,13 +191,330 @@ impl Universe {
         )
     }
 
+    pub fn centre_of_mass_velocity(self: &Universe) -> Velocity {
+        let total_mass = self
+            .bodies
+            .iter()
+            .fold(Mass(0.0), |acc, body| acc + body.mass);
+
+        Velocity(self.momentum().0 / total_mass.0)
+    }
+
+    /// Total angular momentum about the centre of mass.
+    pub fn angular_momentum(self: &Universe) -> AngularMomentum {
+        let centre_of_mass = self.centre_of_mass();
+
+        self.bodies.iter().fold(AngularMomentum(0.0), |acc, body| {
+            let relative_position = (body.position - centre_of_mass).0;
+            acc + AngularMomentum(body.mass.0 * relative_position.cross(body.velocity.0))
+        })
+    }
+
     pub fn kinetic_energy(self: &Universe) -> Energy {
         self.bodies.iter().fold(Energy(0.0), |acc, body| {
-            acc + Energy(body.mass.0 * body.velocity.0.magnitude().powi(2))
+            acc + Energy(0.5 * body.mass.0 * body.velocity.0.magnitude().powi(2))
         })
     }
 
-    // TODO compute kinetic energy
-    // TODO compute potential energy
-    // TODO compute total energy
+    /// The gravitational potential energy summed over every pair of bodies.
+    pub fn potential_energy(self: &Universe) -> Energy {
+        self.bodies
+            .iter()
+            .enumerate()
+            .flat_map(|(object_index, object)| {
+                self.bodies[object_index + 1..]
+                    .iter()
+                    .map(move |subject| (object, subject))
+            })
+            .fold(Energy(0.0), |acc, (object, subject)| {
+                acc + self.gravity.potential_energy(
+                    object.position,
+                    object.mass,
+                    subject.position,
+                    subject.mass,
+                )
+            })
+    }
+
+    pub fn total_energy(self: &Universe) -> Energy {
+        self.kinetic_energy() + self.potential_energy()
+    }
+
+    /// The virial ratio 2K/|W|, using the kinetic energy in the centre of mass frame. A
+    /// self-gravitating system in equilibrium has a ratio of 1, and one with no internal motion,
+    /// such as a lone body, a ratio of 0.
+    pub fn virial_ratio(self: &Universe) -> Scalar {
+        let centre_of_mass_velocity = self.centre_of_mass_velocity();
+
+        let internal_kinetic_energy = self.bodies.iter().fold(0.0, |acc, body| {
+            acc + 0.5
+                * body.mass.0
+                * (body.velocity.0 - centre_of_mass_velocity.0)
+                    .magnitude()
+                    .powi(2)
+        });
+
+        if internal_kinetic_energy == 0.0 {
+            return 0.0;
+        }
+
+        2.0 * internal_kinetic_energy / self.potential_energy().0.abs()
+    }
+
+    /// The body that body `id` is most tightly bound to, ie the one whose two-body orbital energy
+    /// with it is most negative. `None` if it is not bound to any other body, or does not exist.
+    pub fn primary_of(self: &Universe, id: BodyId) -> Option<&Body> {
+        let body = self.body(id)?;
+
+        self.bodies
+            .iter()
+            .filter(|other| other.id != id)
+            .map(|other| {
+                let reduced_mass = body.mass.0 * other.mass.0 / (body.mass + other.mass).0;
+                let speed = (body.velocity.0 - other.velocity.0).magnitude();
+                let distance = (body.position - other.position).0.magnitude();
+
+                let orbital_energy = 0.5 * reduced_mass * speed.powi(2)
+                    - self.gravity.standard_gravitational_parameter(body.mass) * other.mass.0
+                        / distance;
+
+                (other, orbital_energy)
+            })
+            .filter(|(_, orbital_energy)| *orbital_energy < 0.0)
+            .min_by(|(_, left), (_, right)| left.total_cmp(right))
+            .map(|(other, _)| other)
+    }
+
+    /// The total gravitational force on body `id` from every other body, summed exactly.
+    pub fn net_force_on(self: &Universe, id: BodyId) -> Option<Force> {
+        let body = self.body(id)?;
+
+        Some(
+            self.bodies
+                .iter()
+                .filter(|other| other.id != id)
+                .fold(Force(Vector2D::zero()), |acc, other| {
+                    acc + self.gravity.due_to_bodies(body, other)
+                }),
+        )
+    }
+
+    /// The body whose centre is closest to the centre of body `id`, and how far away it is.
+    pub fn nearest_neighbour_of(self: &Universe, id: BodyId) -> Option<(&Body, Scalar)> {
+        let body = self.body(id)?;
+
+        self.bodies
+            .iter()
+            .filter(|other| other.id != id)
+            .map(|other| (other, (other.position - body.position).0.magnitude()))
+            .min_by(|(_, left), (_, right)| left.total_cmp(right))
+    }
+
+    /// The osculating orbital elements of body `id` around its primary, along with the mass of
+    /// the two of them together.
+    pub fn orbit_of(self: &Universe, id: BodyId) -> Option<(OrbitalElements, Mass)> {
+        let primary = self.primary_of(id)?;
+        let body = self.body(id)?;
+
+        OrbitalElements::of(&self.gravity, body, primary)
+            .map(|elements| (elements, body.mass + primary.mass))
+    }
+
+    /// The relative change in total energy, |ΔE/E₀|, since the universe had `initial_energy`. Zero
+    /// when `initial_energy` is, as there is nothing to measure a relative change against.
+    pub fn energy_drift(self: &Universe, initial_energy: Energy) -> Scalar {
+        if initial_energy.0 == 0.0 {
+            return 0.0;
+        }
+
+        ((self.total_energy() - initial_energy).0 / initial_energy.0).abs()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::universe::{Body, BodyId, Universe};
+    use crate::physics::gravity::Gravity;
+    use crate::physics::primitives::{Energy, Mass, Position, Scalar, Vector2D, Velocity};
+    use crate::universes;
+
+    fn two_bodies() -> Universe {
+        Universe::new(
+            Gravity::UNIVERSAL,
+            vec![
+                Body {
+                    id: BodyId::default(),
+                    mass: Mass(2.0),
+                    position: Position(Vector2D::new(0.0, 0.0)),
+                    velocity: Velocity(Vector2D::new(3.0, 4.0)),
+                    radius: 0.0,
+                },
+                Body {
+                    id: BodyId::default(),
+                    mass: Mass(3.0),
+                    position: Position(Vector2D::new(0.0, 2.0)),
+                    velocity: Velocity(Vector2D::new(0.0, 0.0)),
+                    radius: 0.0,
+                },
+            ],
+        )
+    }
+
+    #[test]
+    fn kinetic_energy() {
+        assert_eq!(two_bodies().kinetic_energy(), Energy(25.0));
+    }
+
+    #[test]
+    fn potential_energy() {
+        assert_eq!(
+            two_bodies().potential_energy(),
+            Energy(-6.67430e-11 * 2.0 * 3.0 / 2.0)
+        );
+    }
+
+    #[test]
+    fn angular_momentum_is_about_the_centre_of_mass() {
+        let universe = two_bodies();
+
+        // Centre of mass is at (0, 1.2), so the moving body is 1.2 below it
+        assert!((universe.angular_momentum().0 - 2.0 * 1.2 * 3.0).abs() < 1e-12);
+
+        let shifted = Universe {
+            bodies: universe
+                .bodies
+                .iter()
+                .map(|body| Body {
+                    position: Position(body.position.0 + Vector2D::new(1e3, -1e3)),
+                    ..*body
+                })
+                .collect(),
+            ..universe
+        };
+
+        assert!((shifted.angular_momentum().0 - 2.0 * 1.2 * 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn virial_ratio_of_a_circular_orbit_is_one() {
+        let g: Scalar = 6.67430e-11;
+        let (primary_mass, secondary_mass, separation): (Scalar, Scalar, Scalar) =
+            (1e24, 1e22, 1e8);
+        let total_mass = primary_mass + secondary_mass;
+        let relative_speed = (g * total_mass / separation).sqrt();
+
+        // A uniform drift of the whole system should not affect the ratio
+        let drift = Vector2D::new(1e3, 0.0);
+
+        let universe = Universe::new(
+            Gravity::UNIVERSAL,
+            vec![
+                Body {
+                    id: BodyId::default(),
+                    mass: Mass(primary_mass),
+                    position: Position(Vector2D::new(0.0, 0.0)),
+                    velocity: Velocity(
+                        drift + Vector2D::new(0.0, -relative_speed * secondary_mass / total_mass),
+                    ),
+                    radius: 0.0,
+                },
+                Body {
+                    id: BodyId::default(),
+                    mass: Mass(secondary_mass),
+                    position: Position(Vector2D::new(separation, 0.0)),
+                    velocity: Velocity(
+                        drift + Vector2D::new(0.0, relative_speed * primary_mass / total_mass),
+                    ),
+                    radius: 0.0,
+                },
+            ],
+        );
+
+        assert!((universe.virial_ratio() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn energy_drift() {
+        let universe = two_bodies();
+        let initial_energy = Energy(universe.total_energy().0 * 2.0);
+
+        assert!((universe.energy_drift(initial_energy) - 0.5).abs() < 1e-12);
+
+        let empty = Universe::new(universe.gravity, vec![]);
+        assert_eq!(empty.energy_drift(empty.total_energy()), 0.0);
+    }
+
+    #[test]
+    fn lone_body_has_a_virial_ratio_of_zero() {
+        let mut universe = two_bodies();
+        universe.bodies.truncate(1);
+
+        assert_eq!(universe.virial_ratio(), 0.0);
+    }
+
+    #[test]
+    fn net_force_and_nearest_neighbour() {
+        let mut universe = two_bodies();
+        let far_away = universe.add_body(Body {
+            id: BodyId::default(),
+            mass: Mass(12.0),
+            position: Position(Vector2D::new(0.0, -4.0)),
+            velocity: Velocity(Vector2D::zero()),
+            radius: 0.0,
+        });
+        let (first, second) = (universe.bodies[0].id, universe.bodies[1].id);
+
+        // Pulled up by the 3 kg body 2 m away and down by the 12 kg body 4 m away, equally
+        let force = universe.net_force_on(first).unwrap();
+        assert!(force.0.magnitude() < 1e-25, "{:?}", force.0);
+
+        let (nearest, distance) = universe.nearest_neighbour_of(far_away).unwrap();
+        assert_eq!((nearest.id, distance), (first, 4.0));
+        assert_eq!(universe.nearest_neighbour_of(second).unwrap().0.id, first);
+        assert_eq!(universe.net_force_on(BodyId(99)), None);
+    }
+
+    #[test]
+    fn removed_ids_are_not_reused() {
+        let mut universe = two_bodies();
+        let last = universe.bodies[1];
+
+        assert_eq!(universe.remove_body(last.id), Some(last));
+        assert_eq!(universe.remove_body(last.id), None);
+        assert_eq!(universe.bodies.len(), 1);
+        assert_ne!(universe.add_body(last), last.id);
+    }
+
+    #[test]
+    fn charon_orbits_pluto() {
+        let mut universe = universes::pluto_and_charon();
+        let stray = universe.add_body(Body {
+            id: BodyId::default(),
+            mass: Mass(1e20),
+            position: Position(Vector2D::new(1e10, 0.0)),
+            velocity: Velocity(Vector2D::new(0.0, 1e4)),
+            radius: 0.0,
+        });
+
+        let (pluto, charon) = (universe.bodies[0], universe.bodies[1]);
+
+        assert_eq!(universe.label_of(charon.id), "Charon");
+        assert_eq!(universe.id_named("Charon"), Some(charon.id));
+        assert_eq!(universe.label_of(stray), format!("#{}", stray));
+        assert_eq!(universe.primary_of(charon.id), Some(&pluto));
+        assert_eq!(universe.primary_of(pluto.id), Some(&charon));
+        assert_eq!(universe.primary_of(stray), None);
+
+        let (elements, total_mass) = universe.orbit_of(charon.id).unwrap();
+        assert!((elements.semi_major_axis - 19_591e3).abs() < 1e-3);
+        assert!((elements.eccentricity - 0.0002).abs() < 1e-12);
+
+        // Charon's observed period is 6.387 days
+        let period = elements.period(&universe.gravity, total_mass).0;
+        assert!(
+            (period / (6.387 * 86_400.0) - 1.0).abs() < 1e-3,
+            "{}",
+            period
+        );
+    }
 }
@@ -0,0 +1,111 @@
+use n_body_rust::{BodyId, Scalar, Universe};
+
+use crate::viewport::Viewport;
+
+/// How close to a body, in pixels, a click must land to select it.
+const PICK_DISTANCE: f64 = 6.0;
+
+/// The body under `cursor`, in a window of `window_size`. A click selects the body with the
+/// nearest centre among those it lands on, or within a few pixels of.
+pub fn body_at(
+    universe: &Universe,
+    viewport: &Viewport,
+    window_size: [f64; 2],
+    cursor: [f64; 2],
+) -> Option<BodyId> {
+    let clicked = viewport.convert_from_window(window_size, cursor);
+    let pick_distance = PICK_DISTANCE * (viewport.x_max - viewport.x_min) / window_size[0];
+
+    universe
+        .bodies
+        .iter()
+        .map(|body| (body, (body.position - clicked).0.magnitude()))
+        .filter(|(body, distance)| *distance <= Scalar::max(body.radius, pick_distance))
+        .min_by(|(_, left), (_, right)| left.total_cmp(right))
+        .map(|(body, _)| body.id)
+}
+
+/// A description of body `id` and its surroundings, or nothing if it no longer exists.
+pub fn inspector_lines(universe: &Universe, id: BodyId) -> Vec<String> {
+    let body = match universe.body(id) {
+        Some(body) => body,
+        None => return vec![],
+    };
+
+    let mut lines = vec![
+        match universe.name_of(id) {
+            Some(name) => format!("{} (#{})", name, id),
+            None => format!("#{}", id),
+        },
+        format!("m: {:.4e} kg", body.mass.0),
+        format!(
+            "x: ({:.4e}, {:.4e}) m",
+            body.position.0.x, body.position.0.y
+        ),
+        format!(
+            "v: ({:.4e}, {:.4e}) m/s",
+            body.velocity.0.x, body.velocity.0.y
+        ),
+        format!("|v|: {:.4e} m/s", body.velocity.0.magnitude()),
+    ];
+
+    if let Some(force) = universe.net_force_on(id) {
+        lines.push(format!("F: ({:.4e}, {:.4e}) N", force.0.x, force.0.y));
+    }
+
+    if let Some((neighbour, distance)) = universe.nearest_neighbour_of(id) {
+        lines.push(format!(
+            "nearest: {} at {:.4e} m",
+            universe.label_of(neighbour.id),
+            distance
+        ));
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod test {
+    use crate::inspector::{body_at, inspector_lines};
+    use crate::viewport::Viewport;
+    use n_body_rust::{universes, BodyId};
+
+    #[test]
+    fn test_clicking_a_body_selects_it() {
+        let universe = universes::pluto_and_charon();
+        let viewport = Viewport::square_around(universe.centre_of_mass(), 6e7);
+        let window_size = [600.0, 600.0];
+
+        let charon = universe.bodies[1];
+        let [x, y] = {
+            let (x, y) = viewport.convert_for_window(
+                &piston::RenderArgs {
+                    ext_dt: 0.0,
+                    window_size,
+                    draw_size: [600, 600],
+                },
+                charon.position,
+            );
+            [x, y]
+        };
+
+        assert_eq!(
+            body_at(&universe, &viewport, window_size, [x + 3.0, y - 3.0]),
+            Some(charon.id)
+        );
+        assert_eq!(
+            body_at(&universe, &viewport, window_size, [x - 50.0, y]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_inspector_describes_the_body_and_its_neighbour() {
+        let universe = universes::pluto_and_charon();
+        let lines = inspector_lines(&universe, universe.bodies[1].id);
+
+        assert_eq!(lines[0], "Charon (#1)");
+        assert!(lines.last().unwrap().starts_with("nearest: Pluto at 1.9"));
+        assert!(inspector_lines(&universe, BodyId(7)).is_empty());
+    }
+}
@@ -0,0 +1,53 @@
+//! A simulator for the gravitational n-body problem.
+//!
+//! A [`Universe`] holds a set of [`Body`]s and the [`Gravity`] acting between them. An [`Engine`]
+//! advances a universe through time, using a numerical method to integrate the equations of
+//! motion, a [`ForceSolver`] to compute the forces between bodies and a [`CollisionPolicy`] to
+//! decide what happens when they touch. The [`universes`] module
+//! contains generators for initial conditions, and [`persistence`] saves and loads them along
+//! with [`Checkpoint`]s of whole runs.
+//!
+//! ```
+//! use n_body_rust::{universes, DirectSummation, Engine, Leapfrog, Merge, TemporalDuration};
+//!
+//! let engine = Engine {
+//!     numerical_method: Leapfrog,
+//!     force_solver: DirectSummation,
+//!     collision_policy: Merge,
+//! };
+//!
+//! let mut universe = universes::pluto_and_charon();
+//! for _ in 0..100 {
+//!     universe = engine.step_forward(&universe, TemporalDuration(600.0));
+//! }
+//!
+//! assert_eq!(universe.age, TemporalDuration(60_000.0));
+//! ```
+//!
+//! The graphical viewer is behind the `viewer` feature, which is enabled by default. Depend on
+//! this crate with `default-features = false` to use the simulation without pulling in piston.
+
+pub mod engine;
+pub mod persistence;
+pub mod physics;
+pub mod universes;
+
+pub use engine::checkpoint::Checkpoint;
+pub use engine::events::CollisionEvent;
+pub use engine::metrics::Metric;
+pub use engine::universe::{Body, BodyId, Universe};
+pub use engine::{Engine, StepReport};
+pub use physics::collision::{
+    AnyCollisionPolicy, Bounce, CollisionPolicy, Merge, Outcome, PassThrough,
+};
+pub use physics::force_solvers::{AnyForceSolver, BarnesHut, DirectSummation, ForceSolver};
+pub use physics::gravity::{GravitationalConstant, Gravity, Softening};
+pub use physics::numerical_methods::{
+    AnyNumericalMethod, DormandPrince, EulerMethod, Leapfrog, OdeAlgorithm, PhaseSpace,
+    RungeKutta4, SubSteps,
+};
+pub use physics::orbit::{OrbitDirection, OrbitalElements};
+pub use physics::primitives::{
+    Acceleration, AngularMomentum, Energy, Force, Mass, Momentum, Position, Scalar,
+    TemporalDuration, Vector2D, Velocity,
+};
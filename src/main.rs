@@ -1,39 +1,106 @@
 extern crate graphics;
 
-use std::cmp::{max, min};
 use std::error::Error;
 use std::fmt::{Debug, Display, Formatter};
 use std::ops::Range;
 
-use font_kit::font::Font;
 use font_kit::handle::Handle;
 use font_kit::source::SystemSource;
 use glutin_window::GlutinWindow as Window;
-use graphics::ellipse::circle;
 use graphics::rectangle::centered_square;
-use graphics::{clear, types, CharacterCache, Context, Graphics};
+use graphics::{clear, CharacterCache, Context};
 use opengl_graphics::TextureSettings;
 use opengl_graphics::{GlGraphics, GlyphCache, OpenGL, Texture};
 use piston::event_loop::{EventSettings, Events};
-use piston::input::{RenderArgs, RenderEvent, UpdateArgs, UpdateEvent};
+use piston::input::{
+    Button, Key, MouseButton, MouseCursorEvent, MouseScrollEvent, PressEvent, ReleaseEvent,
+    RenderArgs, RenderEvent, UpdateArgs, UpdateEvent,
+};
 use piston::window::WindowSettings;
 use rand::distributions::Uniform;
 
-use crate::engine::metrics::Metric;
-use crate::engine::universe::{Body, Universe};
-use crate::engine::Engine;
+use crate::camera::{Camera, CameraMode};
 use crate::graphics::Transformed;
-use crate::physics::numerical_methods::OdeAlgorithm;
-use crate::physics::primitives::{Mass, Position, Scalar, TemporalDuration, Vector2D, Velocity};
-use crate::universes::{BoxedVector2DDistribution, CircularVector2DDistribution};
+use crate::inspector::{body_at, inspector_lines};
+use crate::placement::{Placement, DRAG_DURATION};
+use crate::playback::Playback;
+use crate::trails::Trails;
 use crate::viewport::Viewport;
-
-mod engine;
-mod physics;
-mod universes;
+use n_body_rust::engine::events::successor_of;
+use n_body_rust::universes;
+use n_body_rust::universes::{BoxedVector2DDistribution, CircularVector2DDistribution};
+use n_body_rust::{
+    AnyNumericalMethod, BarnesHut, Body, BodyId, CollisionPolicy, DormandPrince, Energy, Engine,
+    ForceSolver, Gravity, Leapfrog, Mass, Merge, Metric, OdeAlgorithm, PhaseSpace, Position,
+    Scalar, Softening, StepReport, SubSteps, TemporalDuration, Universe,
+};
+
+mod camera;
+mod inspector;
+mod placement;
+mod playback;
+mod trails;
 mod viewport;
 
+/// How much one notch of the mouse wheel zooms in or out.
+const ZOOM_PER_SCROLL: Scalar = 1.2;
+
+/// The space left at the right of the window for the inspector panel, in pixels.
+const INSPECTOR_WIDTH: f64 = 220.0;
+
+const USAGE: &str = "Usage: n-body-rust [options]
+
+Options:
+    --scenario <random|pluto-and-charon>   Initial universe (default: random)
+    --seed <n>                             Seed for the random universe (default: chosen at random)
+    --integrator <dormand-prince|leapfrog> Numerical method (default: dormand-prince)
+    --track <name|id>                      Body whose orbit to show
+    --trail-length <n>                     Points kept in each trail (default: 300)
+    --trail-spacing <n>                    Steps between trail points (default: 4)";
+
 fn main() {
+    let (mut universe, viewport_size) = initial_universe_from_args();
+    let tracked_body: Option<BodyId> = arg_value("--track").map(|body| {
+        universe
+            .id_named(&body)
+            .or_else(|| body.parse().ok().map(BodyId))
+            .filter(|id| universe.body(*id).is_some())
+            .unwrap_or_else(|| usage_error("--track must be the name or id of a body"))
+    });
+    let mut metrics = metrics_for(universe.total_energy(), tracked_body);
+    let engine = Engine {
+        numerical_method: numerical_method_from_args(),
+        force_solver: BarnesHut { opening_angle: 0.5 },
+        collision_policy: Merge,
+    };
+    let mut playback = Playback::new(3e4, engine.numerical_method.is_time_reversible());
+
+    let mut camera = Camera::new(
+        Viewport::square_around(universe.centre_of_mass(), viewport_size),
+        CameraMode::CentreOfMass,
+        &universe,
+    );
+    let mut selected_body: Option<BodyId> = tracked_body;
+    let mut trails = Trails::new(
+        arg_value("--trail-length")
+            .map(|length| {
+                length
+                    .parse()
+                    .unwrap_or_else(|_| usage_error("--trail-length must be a whole number"))
+            })
+            .unwrap_or(300),
+        arg_value("--trail-spacing")
+            .map(|spacing| {
+                spacing
+                    .parse()
+                    .unwrap_or_else(|_| usage_error("--trail-spacing must be a whole number"))
+            })
+            .unwrap_or(4),
+    );
+    let mut placement = Placement::new(Mass(1e21), 1400.0);
+    let mut sub_steps = SubSteps::default();
+
+    // Only opens the window once the command line has been understood
     let opengl = OpenGL::V3_2;
 
     let mut window: Window = WindowSettings::new("n-body", [800, 800])
@@ -47,54 +114,152 @@ fn main() {
     let mut graphics = GlGraphics::new(opengl);
     let mut charachter_cache: GlyphCache = make_character_cache().unwrap();
 
-    let mut universe: Universe = universes::random(
-        300,
-        Uniform::new(0.0, 1e21),
-        BoxedVector2DDistribution {
-            x_min: -1e8,
-            x_max: 1e8,
-            y_min: -1e8,
-            y_max: 1e8,
-        },
-        CircularVector2DDistribution {
-            magnitude_min: 0.0,
-            magnitude_max: 5e2,
-        },
-    );
-    let time_scale: Scalar = 3e4;
-    let engine = Engine {
-        numerical_method: physics::numerical_methods::EulerMethod,
-    };
-
-    let viewport_size = 4e8;
-
-    let mut viewport: Viewport = update_viewport_for(&universe, viewport_size);
+    let mut window_size: [f64; 2] = [800.0, 800.0];
+    let mut cursor: [f64; 2] = [0.0, 0.0];
+    let mut panning = false;
 
     while let Some(e) = events.next(&mut window) {
+        let cursor_position = camera.viewport.convert_from_window(window_size, cursor);
+
         if let Some(args) = e.render_args() {
-            render(
-                &mut graphics,
-                &mut charachter_cache,
-                &viewport,
-                &universe,
-                &args,
-            );
+            window_size = args.window_size;
+            let scene = Scene {
+                viewport: &camera.viewport,
+                universe: &universe,
+                trails: &trails,
+                selected_body,
+                placement_preview: placement.preview(cursor_position),
+            };
+            let hud = Hud {
+                lines: metric_lines(&metrics, &universe)
+                    .into_iter()
+                    .chain(status_lines(&universe, &sub_steps, &camera, &playback))
+                    .chain(
+                        placement
+                            .enabled
+                            .then(|| placement.describe(cursor_position)),
+                    )
+                    .collect(),
+                inspector: selected_body
+                    .map(|id| inspector_lines(&universe, id))
+                    .unwrap_or_default(),
+            };
+
+            render(&mut graphics, &mut charachter_cache, &scene, &hud, &args);
+        }
+
+        if let Some(new_cursor) = e.mouse_cursor_args() {
+            if panning {
+                camera.pan(
+                    window_size,
+                    [new_cursor[0] - cursor[0], new_cursor[1] - cursor[1]],
+                );
+            }
+            cursor = new_cursor;
+        }
+
+        if let Some([_, scroll]) = e.mouse_scroll_args() {
+            camera.zoom(window_size, cursor, ZOOM_PER_SCROLL.powf(scroll));
+        }
+
+        match e.press_args() {
+            Some(Button::Mouse(MouseButton::Left)) if placement.enabled => {
+                placement.begin(cursor_position)
+            }
+            Some(Button::Mouse(MouseButton::Left)) => {
+                selected_body = body_at(&universe, &camera.viewport, window_size, cursor)
+            }
+            Some(Button::Mouse(MouseButton::Right | MouseButton::Middle)) => panning = true,
+            Some(Button::Keyboard(Key::A)) => camera.set_mode(CameraMode::FitAll, &universe),
+            Some(Button::Keyboard(Key::C)) => camera.set_mode(CameraMode::CentreOfMass, &universe),
+            Some(Button::Keyboard(Key::F)) => {
+                if let Some(id) = selected_body {
+                    camera.set_mode(CameraMode::Follow(id), &universe)
+                }
+            }
+            Some(Button::Keyboard(Key::N)) => placement.toggle(),
+            Some(Button::Keyboard(Key::LeftBracket)) => placement.lighter(),
+            Some(Button::Keyboard(Key::RightBracket)) => placement.heavier(),
+            Some(Button::Keyboard(Key::Delete | Key::Backspace)) => {
+                if let Some(id) = selected_body.take() {
+                    universe.remove_body(id);
+                    // Energy drift is measured from the universe as it now is
+                    metrics = metrics_for(universe.total_energy(), tracked_body);
+                }
+            }
+            Some(Button::Keyboard(Key::Space)) => playback.toggle_pause(),
+            Some(Button::Keyboard(Key::Period)) => playback.single_step(),
+            Some(Button::Keyboard(Key::Equals | Key::NumPadPlus)) => playback.speed_up(),
+            Some(Button::Keyboard(Key::Minus | Key::NumPadMinus)) => playback.slow_down(),
+            Some(Button::Keyboard(Key::R)) => playback.reverse(),
+            _ => {}
+        }
+
+        match e.release_args() {
+            Some(Button::Mouse(MouseButton::Left)) => {
+                if let Some(body) = placement.finish(cursor_position) {
+                    selected_body = Some(universe.add_body(body));
+                    metrics = metrics_for(universe.total_energy(), tracked_body);
+                }
+            }
+            Some(Button::Mouse(MouseButton::Right | MouseButton::Middle)) => panning = false,
+            _ => {}
         }
 
         if let Some(args) = e.update_args() {
-            universe = ui_driven_update(&engine, time_scale, &universe, &args);
-            viewport = update_viewport_for(&universe, viewport_size);
+            if let Some((new_universe, report)) =
+                ui_driven_update(&engine, &mut playback, &universe, &sub_steps, &args)
+            {
+                universe = new_universe;
+                selected_body = selected_body.and_then(|id| match universe.body(id) {
+                    Some(_) => Some(id),
+                    None => successor_of(id, &report.collisions),
+                });
+                camera.update(&universe, &report.collisions);
+                trails.record(&universe, &report.collisions);
+                sub_steps = report.sub_steps;
+
+                if sub_steps.failed > 0 {
+                    playback.paused = true;
+                }
+            }
         }
     }
 }
 
+/// What is drawn in the window, in the universe's own coordinates.
+struct Scene<'a> {
+    viewport: &'a Viewport,
+    universe: &'a Universe,
+    trails: &'a Trails,
+    selected_body: Option<BodyId>,
+    /// The body being placed, if one is being dragged out.
+    placement_preview: Option<Body>,
+}
+
+/// Text drawn over the scene.
+struct Hud {
+    /// Shown down the left.
+    lines: Vec<String>,
+    /// Describes the selected body, down the right.
+    inspector: Vec<String>,
+}
+
 fn render<C: CharacterCache<Texture = Texture>>(
     graphics: &mut GlGraphics,
     character_cache: &mut C,
-    viewport: &Viewport,
-    universe: &Universe,
+    scene: &Scene,
+    hud: &Hud,
     args: &RenderArgs,
-) -> () {
+) {
+    let Scene {
+        viewport,
+        universe,
+        trails,
+        selected_body,
+        placement_preview,
+    } = *scene;
+
     let min_mass = universe
         .bodies
         .iter()
@@ -108,31 +273,129 @@ fn render<C: CharacterCache<Texture = Texture>>(
         .reduce(|left, right| Mass(Scalar::max(left.0, right.0)))
         .unwrap_or(Mass(0.0));
 
-    let mass_range = (min_mass..max_mass);
+    let mass_range = min_mass..max_mass;
 
     graphics.draw(args.viewport(), |context, graphics| {
         clear([0.0, 0.0, 0.0, 1.0], graphics);
 
-        draw_metrics(
+        draw_hud(graphics, context, character_cache, &hud.lines, 10.0).unwrap();
+        draw_hud(
             graphics,
             context,
             character_cache,
-            universe,
-            [Metric::NumBodies, Metric::KineticEnergy, Metric::Momentum],
+            &hud.inspector,
+            args.window_size[0] - INSPECTOR_WIDTH,
         )
         .unwrap();
 
+        for (id, trail) in trails.iter() {
+            draw_trail(
+                graphics,
+                context,
+                viewport,
+                args,
+                trail
+                    .iter()
+                    .chain(universe.body(*id).map(|body| &body.position)),
+            );
+        }
+
         for body in &universe.bodies {
             let (window_x, window_y) = viewport.convert_for_window(args, body.position);
 
-            let circle =
-                centered_square(window_x, window_y, scale_radius_by(&mass_range, body.mass));
+            let radius = window_radius(viewport, args, &mass_range, body);
+            let circle = centered_square(window_x, window_y, radius);
 
             graphics::ellipse([1.0, 1.0, 1.0, 1.0], circle, context.transform, graphics);
+
+            if selected_body == Some(body.id) {
+                graphics::Ellipse::new_border([1.0, 0.8, 0.2, 1.0], 1.0).draw(
+                    centered_square(window_x, window_y, radius + 4.0),
+                    &context.draw_state,
+                    context.transform,
+                    graphics,
+                );
+            }
+        }
+
+        if let Some(body) = placement_preview {
+            draw_placement_preview(graphics, context, viewport, args, &body);
         }
     });
 }
 
+fn metrics_for(initial_energy: Energy, tracked_body: Option<BodyId>) -> Vec<Metric> {
+    let mut metrics = vec![
+        Metric::NumBodies,
+        Metric::KineticEnergy,
+        Metric::PotentialEnergy,
+        Metric::TotalEnergy,
+        Metric::EnergyDrift(initial_energy),
+        Metric::Momentum,
+        Metric::AngularMomentum,
+        Metric::VirialRatio,
+    ];
+
+    if let Some(id) = tracked_body {
+        metrics.extend([
+            Metric::SemiMajorAxis(id),
+            Metric::Eccentricity(id),
+            Metric::OrbitalPeriod(id),
+        ]);
+    }
+
+    metrics
+}
+
+fn status_lines(
+    universe: &Universe,
+    sub_steps: &SubSteps,
+    camera: &Camera,
+    playback: &Playback,
+) -> Vec<String> {
+    let mut lines = vec![
+        format!("t: {:e} s, {}", universe.age.0, playback.describe()),
+        format!(
+            "sub-steps: {} accepted, {} rejected",
+            sub_steps.accepted, sub_steps.rejected
+        ),
+        match camera.mode {
+            CameraMode::CentreOfMass => "camera: centre of mass".to_string(),
+            CameraMode::Follow(id) => format!("camera: following {}", universe.label_of(id)),
+            CameraMode::FitAll => "camera: fitting all bodies".to_string(),
+            CameraMode::Free => "camera: free".to_string(),
+        },
+    ];
+
+    if sub_steps.failed > 0 {
+        lines.push("paused: the integrator could not keep within its tolerances".to_string());
+    }
+
+    if playback.reverse_refused {
+        lines.push("cannot reverse: integrator is not time-reversible".to_string());
+    }
+
+    if let Some(seed) = universe.seed {
+        lines.push(format!("seed: {}", seed));
+    }
+
+    lines
+}
+
+/// Bodies are drawn at their physical size once zoomed in far enough to see it, and otherwise with
+/// a radius that grows with their mass.
+fn window_radius(
+    viewport: &Viewport,
+    args: &RenderArgs,
+    all_masses: &Range<Mass>,
+    body: &Body,
+) -> graphics::math::Scalar {
+    Scalar::max(
+        viewport.length_for_window(args, body.radius),
+        scale_radius_by(all_masses, body.mass),
+    )
+}
+
 fn scale_radius_by(all_masses: &Range<Mass>, mass: Mass) -> graphics::math::Scalar {
     const MAX_RADIUS: graphics::math::Scalar = 5.0;
     const MIN_RADIUS: graphics::math::Scalar = 1.0;
@@ -148,41 +411,172 @@ fn scale_radius_by(all_masses: &Range<Mass>, mass: Mass) -> graphics::math::Scal
     }
 }
 
-fn ui_driven_update<A: OdeAlgorithm<Vector2D, Scalar>>(
-    engine: &engine::Engine<A>,
-    time_scale: Scalar,
+fn ui_driven_update<A: OdeAlgorithm<PhaseSpace, Scalar>, S: ForceSolver, C: CollisionPolicy>(
+    engine: &Engine<A, S, C>,
+    playback: &mut Playback,
     old_universe: &Universe,
+    previous: &SubSteps,
     args: &UpdateArgs,
-) -> Universe {
+) -> Option<(Universe, StepReport)> {
     let ui_dt = TemporalDuration(args.dt);
-    let dt = ui_dt * time_scale;
-    engine.step_forward(old_universe, dt)
+    let dt = playback.dt_for(ui_dt)?;
+    Some(engine.step_forward_after(old_universe, dt, previous))
+}
+
+/// The integrator chosen with `--integrator`. Only leapfrog can run backwards.
+fn numerical_method_from_args() -> AnyNumericalMethod {
+    match arg_value("--integrator").as_deref() {
+        Some("dormand-prince") | None => AnyNumericalMethod::DormandPrince(DormandPrince {
+            position_tolerance: 1.0,
+            velocity_tolerance: 1e-3,
+            relative_tolerance: 1e-6,
+        }),
+        Some("leapfrog") => AnyNumericalMethod::Leapfrog(Leapfrog),
+        Some(other) => usage_error(&format!("Unknown integrator {}", other)),
+    }
+}
+
+/// The universe chosen with `--scenario`, and how much of it to show. A random universe can be
+/// generated again by passing the seed it showed with `--seed`.
+fn initial_universe_from_args() -> (Universe, Scalar) {
+    match arg_value("--scenario").as_deref() {
+        Some("pluto-and-charon") => (universes::pluto_and_charon(), 6e7),
+        Some("random") | None => {
+            let seed = arg_value("--seed")
+                .map(|seed| {
+                    seed.parse()
+                        .unwrap_or_else(|_| usage_error("--seed must be a whole number"))
+                })
+                .unwrap_or_else(rand::random);
+
+            let universe = Universe {
+                gravity: Gravity {
+                    softening: Softening::Plummer { length: 1e6 },
+                    ..Gravity::UNIVERSAL
+                },
+                ..universes::seeded_random(
+                    seed,
+                    300,
+                    Uniform::new(0.0, 1e21),
+                    BoxedVector2DDistribution {
+                        x_min: -1e8,
+                        x_max: 1e8,
+                        y_min: -1e8,
+                        y_max: 1e8,
+                    },
+                    CircularVector2DDistribution {
+                        magnitude_min: 0.0,
+                        magnitude_max: 5e2,
+                    },
+                    1400.0,
+                )
+            };
+
+            (universe, 4e8)
+        }
+        Some(other) => usage_error(&format!("Unknown scenario {}", other)),
+    }
+}
+
+/// Explains what was wrong with the command line and how to use it, then exits.
+fn usage_error(message: &str) -> ! {
+    eprintln!("{}\n\n{}", message, USAGE);
+    std::process::exit(1);
 }
 
-fn update_viewport_for(universe: &Universe, viewport_size: Scalar) -> Viewport {
-    let centre_of_mass = universe.centre_of_mass();
+/// The value following `name` on the command line, if it was given.
+fn arg_value(name: &str) -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
 
-    Viewport::square_around(centre_of_mass, viewport_size)
+    args.windows(2)
+        .find(|pair| pair[0] == name)
+        .map(|pair| pair[1].clone())
 }
 
-fn draw_metrics<C: CharacterCache<Texture = Texture>, const N: usize>(
+/// Draws the body being placed, with a line to where its velocity would take it.
+fn draw_placement_preview(
     graphics: &mut GlGraphics,
     context: Context,
-    character_cache: &mut C,
-    universe: &Universe,
-    metrics: [Metric; N],
-) -> Result<(), ()> {
-    let lines = metrics
+    viewport: &Viewport,
+    args: &RenderArgs,
+    body: &Body,
+) {
+    const COLOUR: [f32; 4] = [0.4, 0.8, 1.0, 0.8];
+
+    let (start_x, start_y) = viewport.convert_for_window(args, body.position);
+    let (end_x, end_y) = viewport.convert_for_window(
+        args,
+        Position(body.position.0 + body.velocity.0 * DRAG_DURATION),
+    );
+    let radius = Scalar::max(viewport.length_for_window(args, body.radius), 3.0);
+
+    graphics::ellipse(
+        COLOUR,
+        centered_square(start_x, start_y, radius),
+        context.transform,
+        graphics,
+    );
+    graphics::line_from_to(
+        COLOUR,
+        0.75,
+        [start_x, start_y],
+        [end_x, end_y],
+        context.transform,
+        graphics,
+    );
+}
+
+/// Draws a line through `positions`, oldest first, fading out towards the oldest.
+fn draw_trail<'a>(
+    graphics: &mut GlGraphics,
+    context: Context,
+    viewport: &Viewport,
+    args: &RenderArgs,
+    positions: impl Iterator<Item = &'a Position>,
+) {
+    const MAX_OPACITY: f32 = 0.5;
+
+    let window_positions: Vec<(f64, f64)> = positions
+        .map(|position| viewport.convert_for_window(args, *position))
+        .collect();
+    let segments = window_positions.len().saturating_sub(1);
+
+    for (index, pair) in window_positions.windows(2).enumerate() {
+        let opacity = MAX_OPACITY * (index + 1) as f32 / segments as f32;
+
+        graphics::line_from_to(
+            [1.0, 1.0, 1.0, opacity],
+            0.5,
+            [pair[0].0, pair[0].1],
+            [pair[1].0, pair[1].1],
+            context.transform,
+            graphics,
+        );
+    }
+}
+
+fn metric_lines(metrics: &[Metric], universe: &Universe) -> Vec<String> {
+    metrics
         .iter()
-        .map(|m| format!("{}: {}", m.symbol(), m.compute_from(universe)));
+        .map(|m| format!("{}: {}", m.symbol(), m.compute_from(universe)))
+        .collect()
+}
 
-    for (index, line) in lines.enumerate() {
+/// Draws `lines` of text from the top of the window, starting `x` from its left.
+fn draw_hud<C: CharacterCache<Texture = Texture>>(
+    graphics: &mut GlGraphics,
+    context: Context,
+    character_cache: &mut C,
+    lines: &[String],
+    x: f64,
+) -> Result<(), ()> {
+    for (index, line) in lines.iter().enumerate() {
         graphics::text(
             graphics::color::WHITE,
             10,
-            &line,
+            line,
             character_cache,
-            context.trans(10.0, ((index + 1) as f64) * 10.0).transform,
+            context.trans(x, ((index + 1) as f64) * 10.0).transform,
             graphics,
         )
         .map_err(|_| ())?
@@ -208,6 +602,7 @@ fn make_character_cache<'a>() -> Result<GlyphCache<'a>, CharCacheError> {
 }
 
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 enum CharCacheError {
     SelectFontError(font_kit::error::SelectionError),
     FontInMemoryError,
@@ -216,7 +611,13 @@ enum CharCacheError {
 
 impl Display for CharCacheError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        Debug::fmt(self, f)
+        match self {
+            CharCacheError::SelectFontError(error) => {
+                write!(f, "could not find the ArialMT font: {}", error)
+            }
+            CharCacheError::FontInMemoryError => write!(f, "the ArialMT font is not in a file"),
+            CharCacheError::GlyphCacheError => write!(f, "could not load the ArialMT font"),
+        }
     }
 }
 
@@ -0,0 +1,313 @@
+//! Saving and loading simulation state as JSON or RON files. The format is chosen from the file
+//! extension, `.json` or `.ron`.
+
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::engine::checkpoint::Checkpoint;
+use crate::engine::universe::{BodyId, Universe};
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Format {
+    Json,
+    Ron,
+}
+
+impl Format {
+    pub fn for_path(path: &Path) -> Result<Format, PersistenceError> {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => Ok(Format::Json),
+            Some("ron") => Ok(Format::Ron),
+            _ => Err(PersistenceError::UnknownFormat {
+                path: path.to_path_buf(),
+            }),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum PersistenceError {
+    UnknownFormat {
+        path: PathBuf,
+    },
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    Json {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    Ron {
+        path: PathBuf,
+        source: ron::Error,
+    },
+    /// More than one body in the file has this id.
+    DuplicateBodyId {
+        path: PathBuf,
+        id: BodyId,
+    },
+}
+
+impl Display for PersistenceError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PersistenceError::UnknownFormat { path } => write!(
+                f,
+                "Cannot tell the format of {}, expected a .json or .ron extension",
+                path.display()
+            ),
+            PersistenceError::Io { path, source } => {
+                write!(f, "Could not access {}: {}", path.display(), source)
+            }
+            PersistenceError::Json { path, source } => {
+                write!(f, "Invalid JSON in {}: {}", path.display(), source)
+            }
+            PersistenceError::Ron { path, source } => {
+                write!(f, "Invalid RON in {}: {}", path.display(), source)
+            }
+            PersistenceError::DuplicateBodyId { path, id } => write!(
+                f,
+                "More than one body in {} has the id {}",
+                path.display(),
+                id
+            ),
+        }
+    }
+}
+
+impl Error for PersistenceError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PersistenceError::UnknownFormat { .. } => None,
+            PersistenceError::Io { source, .. } => Some(source),
+            PersistenceError::Json { source, .. } => Some(source),
+            PersistenceError::Ron { source, .. } => Some(source),
+            PersistenceError::DuplicateBodyId { .. } => None,
+        }
+    }
+}
+
+pub fn save_universe(path: &Path, universe: &Universe) -> Result<(), PersistenceError> {
+    save(path, universe)
+}
+
+pub fn load_universe(path: &Path) -> Result<Universe, PersistenceError> {
+    let mut universe: Universe = load(path)?;
+    assign_missing_ids(path, &mut universe)?;
+    Ok(universe)
+}
+
+pub fn save_checkpoint(path: &Path, checkpoint: &Checkpoint) -> Result<(), PersistenceError> {
+    save(path, checkpoint)
+}
+
+pub fn load_checkpoint(path: &Path) -> Result<Checkpoint, PersistenceError> {
+    let mut checkpoint: Checkpoint = load(path)?;
+    assign_missing_ids(path, &mut checkpoint.universe)?;
+    Ok(checkpoint)
+}
+
+fn assign_missing_ids(path: &Path, universe: &mut Universe) -> Result<(), PersistenceError> {
+    universe
+        .assign_missing_ids()
+        .map_err(|id| PersistenceError::DuplicateBodyId {
+            path: path.to_path_buf(),
+            id,
+        })
+}
+
+/// Writes any serializable value to `path`, in the format given by its extension.
+pub fn save<T: Serialize>(path: &Path, value: &T) -> Result<(), PersistenceError> {
+    let contents =
+        match Format::for_path(path)? {
+            Format::Json => {
+                serde_json::to_string_pretty(value).map_err(|source| PersistenceError::Json {
+                    path: path.to_path_buf(),
+                    source,
+                })?
+            }
+            Format::Ron => ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::new())
+                .map_err(|source| PersistenceError::Ron {
+                    path: path.to_path_buf(),
+                    source,
+                })?,
+        };
+
+    // Write alongside and then move into place, so a crash part way through never leaves a
+    // truncated file where a good one used to be
+    let partial_path = path.with_file_name(format!(
+        ".{}.partial",
+        path.file_name().unwrap_or_default().to_string_lossy()
+    ));
+
+    fs::write(&partial_path, contents)
+        .and_then(|_| fs::rename(&partial_path, path))
+        .map_err(|source| PersistenceError::Io {
+            path: path.to_path_buf(),
+            source,
+        })
+}
+
+/// Reads a value from `path`, in the format given by its extension.
+pub fn load<T: DeserializeOwned>(path: &Path) -> Result<T, PersistenceError> {
+    let format = Format::for_path(path)?;
+
+    let contents = fs::read_to_string(path).map_err(|source| PersistenceError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    match format {
+        Format::Json => serde_json::from_str(&contents).map_err(|source| PersistenceError::Json {
+            path: path.to_path_buf(),
+            source,
+        }),
+        Format::Ron => ron::de::from_str(&contents).map_err(|source| PersistenceError::Ron {
+            path: path.to_path_buf(),
+            source,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use rand::distributions::Uniform;
+
+    use crate::engine::universe::{BodyId, Universe};
+    use crate::persistence::{load_universe, save_universe, PersistenceError};
+    use crate::physics::gravity::{Gravity, Softening};
+    use crate::physics::primitives::TemporalDuration;
+    use crate::universes;
+    use crate::universes::{BoxedVector2DDistribution, CircularVector2DDistribution};
+
+    fn temporary_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("n-body-rust-{}-{}", std::process::id(), name))
+    }
+
+    fn random_universe() -> Universe {
+        Universe {
+            gravity: Gravity {
+                softening: Softening::Spline { length: 1e6 },
+                ..Gravity::UNIVERSAL
+            },
+            age: TemporalDuration(1234.5678),
+            ..universes::random(
+                &mut universes::seeded_rng(5),
+                50,
+                Uniform::new(0.0, 1e21),
+                BoxedVector2DDistribution {
+                    x_min: -1e8,
+                    x_max: 1e8,
+                    y_min: -1e8,
+                    y_max: 1e8,
+                },
+                CircularVector2DDistribution {
+                    magnitude_min: 0.0,
+                    magnitude_max: 5e2,
+                },
+                1400.0,
+            )
+        }
+    }
+
+    #[test]
+    fn universe_round_trips_exactly() {
+        let universe = random_universe();
+
+        for name in ["universe.json", "universe.ron"] {
+            let path = temporary_path(name);
+
+            save_universe(&path, &universe).unwrap();
+            let loaded = load_universe(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+
+            assert_eq!(loaded, universe);
+        }
+    }
+
+    #[test]
+    fn malformed_file_reports_path_and_problem() {
+        let path = temporary_path("malformed.json");
+        std::fs::write(&path, r#"{ "gravity": { "gravitational_constant": 1.0 } }"#).unwrap();
+
+        let error = load_universe(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(error, PersistenceError::Json { .. }));
+        let message = error.to_string();
+        assert!(message.contains("malformed.json"), "{}", message);
+        assert!(message.contains("softening"), "{}", message);
+    }
+
+    #[test]
+    fn bodies_saved_without_ids_are_numbered() {
+        let path = temporary_path("without-ids.json");
+        let mut universe = universes::pluto_and_charon();
+        let mut json = serde_json::to_value(&universe).unwrap();
+        for body in json["bodies"].as_array_mut().unwrap() {
+            body.as_object_mut().unwrap().remove("id");
+        }
+        json.as_object_mut().unwrap().remove("next_body_id");
+        std::fs::write(&path, json.to_string()).unwrap();
+
+        let mut loaded = load_universe(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded, universe);
+        assert_eq!(
+            loaded.add_body(loaded.bodies[0]),
+            universe.add_body(universe.bodies[0])
+        );
+    }
+
+    #[test]
+    fn bodies_keep_their_ids_when_only_some_are_saved() {
+        let path = temporary_path("some-ids.json");
+        let universe = universes::pluto_and_charon();
+        let mut json = serde_json::to_value(&universe).unwrap();
+        json["bodies"][0]["id"] = serde_json::json!(1);
+        json["bodies"][1]["id"] = serde_json::json!(2);
+        json["names"] = serde_json::json!({ "2": "Charon" });
+        json.as_object_mut().unwrap().remove("next_body_id");
+        std::fs::write(&path, json.to_string()).unwrap();
+
+        let mut loaded = load_universe(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.bodies[0].id, BodyId(1));
+        assert_eq!(loaded.bodies[1].id, BodyId(2));
+        assert_eq!(loaded.id_named("Charon"), Some(loaded.bodies[1].id));
+        assert_eq!(loaded.add_body(loaded.bodies[0]), BodyId(3));
+    }
+
+    #[test]
+    fn bodies_sharing_an_id_are_rejected() {
+        let path = temporary_path("duplicate-ids.json");
+        let mut json = serde_json::to_value(universes::pluto_and_charon()).unwrap();
+        json["bodies"][1]["id"] = json["bodies"][0]["id"].clone();
+        std::fs::write(&path, json.to_string()).unwrap();
+
+        let error = load_universe(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(
+            error,
+            PersistenceError::DuplicateBodyId { id: BodyId(0), .. }
+        ));
+    }
+
+    #[test]
+    fn unknown_extension_is_rejected() {
+        let error = load_universe(&PathBuf::from("universe.txt")).unwrap_err();
+
+        assert!(matches!(error, PersistenceError::UnknownFormat { .. }));
+    }
+}
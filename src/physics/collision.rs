@@ -1,28 +1,404 @@
+use serde::{Deserialize, Serialize};
+
 use crate::engine::universe::Body;
-use crate::physics::primitives::{Position, Scalar, TemporalDuration, Vector2D, Velocity};
+use crate::physics::primitives::{Mass, Momentum, Position, Scalar, Vector2D, Velocity};
 
-pub fn collide(left: &Body, right: &Body, dt: TemporalDuration) -> Option<Body> {
-    if (left.position - right.position).0.magnitude() <= collision_radius(left, right) {
-        let new_mass = left.mass + right.mass;
-        let new_position = Position(
-            (left.mass.0 * left.position.0 + right.mass.0 * right.position.0) / new_mass.0,
-        );
-        let new_velocity = Velocity(
-            (left.mass.0 * left.velocity.0 + right.mass.0 * right.velocity.0) / new_mass.0,
-        );
+/// Decides what becomes of bodies whose surfaces touch.
+pub trait CollisionPolicy: Sync {
+    fn resolve(&self, left: &Body, right: &Body) -> Outcome;
+
+    /// Resolves a group of bodies that are all touching, directly or through each other, returning
+    /// whichever bodies remain. By default each touching pair is resolved in turn, in order.
+    fn resolve_group(&self, group: &[Body]) -> Vec<Body> {
+        let mut remaining_bodies: Vec<Option<Body>> = group.iter().copied().map(Some).collect();
+
+        for left_index in 0..remaining_bodies.len() {
+            for right_index in left_index + 1..remaining_bodies.len() {
+                let (left, right) =
+                    match (remaining_bodies[left_index], remaining_bodies[right_index]) {
+                        (Some(left), Some(right)) if touching(&left, &right) => (left, right),
+                        _ => continue,
+                    };
+
+                match self.resolve(&left, &right) {
+                    Outcome::Merged(merged) => {
+                        remaining_bodies[left_index] = Some(merged);
+                        remaining_bodies[right_index] = None;
+                    }
+                    Outcome::Separate(new_left, new_right) => {
+                        remaining_bodies[left_index] = Some(new_left);
+                        remaining_bodies[right_index] = Some(new_right);
+                    }
+                }
+            }
+        }
+
+        remaining_bodies.into_iter().flatten().collect()
+    }
+}
+
+/// The result of a collision between two bodies.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Outcome {
+    /// The two bodies became one.
+    Merged(Body),
+    /// The two bodies carried on separately, as `left` and `right` respectively.
+    Separate(Body, Body),
+}
+
+/// A perfectly inelastic collision, where the bodies become a single body with their combined
+/// mass, momentum and volume. The merged body keeps the id of the most massive body, or of the
+/// lowest id among the most massive if several are equally heavy.
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+pub struct Merge;
+
+impl CollisionPolicy for Merge {
+    fn resolve(&self, left: &Body, right: &Body) -> Outcome {
+        Outcome::Merged(merge_all(&[*left, *right]))
+    }
+
+    /// The whole group becomes one body at once, so the result does not depend on which bodies
+    /// touched which.
+    fn resolve_group(&self, group: &[Body]) -> Vec<Body> {
+        vec![merge_all(group)]
+    }
+}
+
+fn merge_all(bodies: &[Body]) -> Body {
+    let new_mass = bodies.iter().fold(Mass(0.0), |acc, body| acc + body.mass);
+    let weighted_positions = bodies.iter().fold(Vector2D::zero(), |acc, body| {
+        acc + body.mass.0 * body.position.0
+    });
+    let momentum = bodies.iter().fold(Momentum(Vector2D::zero()), |acc, body| {
+        acc + body.momentum()
+    });
+    let volume = bodies
+        .iter()
+        .fold(0.0, |acc: Scalar, body| acc + body.radius.powi(3));
+    let heaviest = bodies
+        .iter()
+        .min_by(|left, right| {
+            right
+                .mass
+                .0
+                .total_cmp(&left.mass.0)
+                .then(left.id.cmp(&right.id))
+        })
+        .expect("cannot merge no bodies");
+
+    Body {
+        id: heaviest.id,
+        mass: new_mass,
+        position: Position(weighted_positions / new_mass.0),
+        velocity: Velocity(momentum.0 / new_mass.0),
+        radius: volume.cbrt(),
+    }
+}
+
+/// The bodies bounce off each other. The `restitution` is the ratio of their speed of separation
+/// to their speed of approach: one for an elastic collision that conserves kinetic energy, and
+/// less than one for an inelastic collision.
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+pub struct Bounce {
+    pub restitution: Scalar,
+}
+
+impl Bounce {
+    /// The impulse that `right` gives `left`, which gives `right` the opposite.
+    fn impulse(self: &Bounce, left: &Body, right: &Body) -> Vector2D {
+        let normal = (left.position - right.position).0.unit();
+        let approach_speed = -(left.velocity.0 - right.velocity.0).dot(normal);
+
+        // Bodies that are already moving apart, or exactly coincide, are left to separate
+        if approach_speed.is_nan() || approach_speed <= 0.0 {
+            return Vector2D::zero();
+        }
+
+        (1.0 + self.restitution) * approach_speed / (1.0 / left.mass.0 + 1.0 / right.mass.0)
+            * normal
+    }
+}
+
+impl CollisionPolicy for Bounce {
+    fn resolve(&self, left: &Body, right: &Body) -> Outcome {
+        let impulse = self.impulse(left, right);
+
+        Outcome::Separate(
+            with_impulse(left, impulse),
+            with_impulse(right, impulse * -1.0),
+        )
+    }
+
+    /// Every touching pair's impulse is worked out from the velocities before the collision, and
+    /// they are all applied at once, so the result does not depend on the order of the bodies.
+    fn resolve_group(&self, group: &[Body]) -> Vec<Body> {
+        let mut impulses = vec![Vector2D::zero(); group.len()];
+
+        for left_index in 0..group.len() {
+            for right_index in left_index + 1..group.len() {
+                let (left, right) = (&group[left_index], &group[right_index]);
+                if touching(left, right) {
+                    let impulse = self.impulse(left, right);
+                    impulses[left_index] = impulses[left_index] + impulse;
+                    impulses[right_index] = impulses[right_index] - impulse;
+                }
+            }
+        }
+
+        group
+            .iter()
+            .zip(impulses)
+            .map(|(body, impulse)| with_impulse(body, impulse))
+            .collect()
+    }
+}
+
+fn with_impulse(body: &Body, impulse: Vector2D) -> Body {
+    Body {
+        velocity: Velocity(body.velocity.0 + impulse / body.mass.0),
+        ..*body
+    }
+}
+
+/// The bodies pass through each other untouched, as if they were point masses.
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+pub struct PassThrough;
+
+impl CollisionPolicy for PassThrough {
+    fn resolve(&self, left: &Body, right: &Body) -> Outcome {
+        Outcome::Separate(*left, *right)
+    }
+
+    fn resolve_group(&self, group: &[Body]) -> Vec<Body> {
+        group.to_vec()
+    }
+}
+
+/// Any one of the collision policies, chosen at runtime.
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+pub enum AnyCollisionPolicy {
+    Merge(Merge),
+    Bounce(Bounce),
+    PassThrough(PassThrough),
+}
 
-        let new_body: Body = Body {
-            mass: new_mass,
-            position: new_position,
-            velocity: new_velocity,
-        };
+impl CollisionPolicy for AnyCollisionPolicy {
+    fn resolve(&self, left: &Body, right: &Body) -> Outcome {
+        match self {
+            AnyCollisionPolicy::Merge(policy) => policy.resolve(left, right),
+            AnyCollisionPolicy::Bounce(policy) => policy.resolve(left, right),
+            AnyCollisionPolicy::PassThrough(policy) => policy.resolve(left, right),
+        }
+    }
+
+    fn resolve_group(&self, group: &[Body]) -> Vec<Body> {
+        match self {
+            AnyCollisionPolicy::Merge(policy) => policy.resolve_group(group),
+            AnyCollisionPolicy::Bounce(policy) => policy.resolve_group(group),
+            AnyCollisionPolicy::PassThrough(policy) => policy.resolve_group(group),
+        }
+    }
+}
+
+/// Whether the surfaces of two bodies meet or overlap.
+pub fn touching(left: &Body, right: &Body) -> bool {
+    (left.position - right.position).0.magnitude() <= left.radius + right.radius
+}
+
+/// Partitions `bodies` into groups that touch, directly or through other bodies in the group.
+/// Each group is a list of indexes into `bodies` in ascending order, and the groups are ordered by
+/// their first index. A body touching nothing is a group of its own.
+pub fn touching_groups(bodies: &[Body]) -> Vec<Vec<usize>> {
+    // A union-find forest, where each group is identified by its lowest index
+    let mut parents: Vec<usize> = (0..bodies.len()).collect();
+
+    fn root(parents: &mut [usize], index: usize) -> usize {
+        let mut root = index;
+        while parents[root] != root {
+            root = parents[root];
+        }
+
+        let mut current = index;
+        while parents[current] != root {
+            current = std::mem::replace(&mut parents[current], root);
+        }
+
+        root
+    }
+
+    for left_index in 0..bodies.len() {
+        for right_index in left_index + 1..bodies.len() {
+            if touching(&bodies[left_index], &bodies[right_index]) {
+                let (left_root, right_root) = (
+                    root(&mut parents, left_index),
+                    root(&mut parents, right_index),
+                );
+                parents[left_root.max(right_root)] = left_root.min(right_root);
+            }
+        }
+    }
+
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    let mut group_of_root: Vec<Option<usize>> = vec![None; bodies.len()];
+
+    for index in 0..bodies.len() {
+        let root = root(&mut parents, index);
 
-        Some(new_body)
-    } else {
-        None
+        match group_of_root[root] {
+            Some(group) => groups[group].push(index),
+            None => {
+                group_of_root[root] = Some(groups.len());
+                groups.push(vec![index]);
+            }
+        }
     }
+
+    groups
 }
 
-fn collision_radius(left: &Body, right: &Body) -> Scalar {
-    (left.mass.0 + right.mass.0).cbrt() / 18.08
+#[cfg(test)]
+mod tests {
+    use crate::engine::universe::{Body, BodyId};
+    use crate::physics::collision::{
+        touching, Bounce, CollisionPolicy, Merge, Outcome, PassThrough,
+    };
+    use crate::physics::primitives::{Mass, Momentum, Position, Scalar, Vector2D, Velocity};
+
+    fn body_at(x: Scalar, radius: Scalar) -> Body {
+        Body {
+            id: BodyId(0),
+            mass: Mass(1e20),
+            position: Position(Vector2D::new(x, 0.0)),
+            velocity: Velocity(Vector2D::zero()),
+            radius,
+        }
+    }
+
+    /// Two bodies of different masses meeting at an angle
+    fn head_on() -> (Body, Body) {
+        (
+            Body {
+                id: BodyId(0),
+                mass: Mass(2e20),
+                position: Position(Vector2D::new(0.0, 0.0)),
+                velocity: Velocity(Vector2D::new(30.0, 5.0)),
+                radius: 3.0,
+            },
+            Body {
+                id: BodyId(1),
+                mass: Mass(5e20),
+                position: Position(Vector2D::new(4.0, 3.0)),
+                velocity: Velocity(Vector2D::new(-10.0, -2.0)),
+                radius: 2.0,
+            },
+        )
+    }
+
+    fn momentum(outcome: Outcome) -> Momentum {
+        match outcome {
+            Outcome::Merged(body) => body.momentum(),
+            Outcome::Separate(left, right) => left.momentum() + right.momentum(),
+        }
+    }
+
+    fn kinetic_energy(body: &Body) -> Scalar {
+        0.5 * body.mass.0 * body.velocity.0.magnitude().powi(2)
+    }
+
+    fn assert_momentum_conserved(outcome: Outcome, left: &Body, right: &Body) {
+        let before = (left.momentum() + right.momentum()).0;
+        let after = momentum(outcome).0;
+
+        assert!(
+            (after - before).magnitude() <= 1e-12 * before.magnitude(),
+            "momentum was {} but is now {}",
+            before,
+            after
+        );
+    }
+
+    #[test]
+    fn bodies_touch_only_when_surfaces_overlap() {
+        assert!(!touching(&body_at(0.0, 3.0), &body_at(5.0, 1.9)));
+        assert!(touching(&body_at(0.0, 3.0), &body_at(5.0, 2.0)));
+        assert!(!touching(&body_at(0.0, 0.0), &body_at(1e-9, 0.0)));
+    }
+
+    #[test]
+    fn merging_conserves_momentum_and_volume() {
+        let (left, right) = head_on();
+        let outcome = Merge.resolve(&left, &right);
+
+        assert_momentum_conserved(outcome, &left, &right);
+        match outcome {
+            Outcome::Merged(merged) => {
+                assert_eq!(merged.mass, Mass(7e20));
+                assert_eq!(merged.id, right.id);
+                assert!((merged.radius.powi(3) - (27.0 + 8.0)).abs() < 1e-9);
+            }
+            other => panic!("expected a merge but got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn elastic_bounce_conserves_momentum_and_energy() {
+        let (left, right) = head_on();
+        let outcome = Bounce { restitution: 1.0 }.resolve(&left, &right);
+
+        assert_momentum_conserved(outcome, &left, &right);
+        match outcome {
+            Outcome::Separate(new_left, new_right) => {
+                let before = kinetic_energy(&left) + kinetic_energy(&right);
+                let after = kinetic_energy(&new_left) + kinetic_energy(&new_right);
+                assert!((after / before - 1.0).abs() < 1e-12);
+            }
+            other => panic!("expected a bounce but got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn inelastic_bounce_conserves_momentum_and_scales_separation_speed() {
+        let (left, right) = head_on();
+        let normal = (left.position - right.position).0.unit();
+        let outcome = Bounce { restitution: 0.5 }.resolve(&left, &right);
+
+        assert_momentum_conserved(outcome, &left, &right);
+        match outcome {
+            Outcome::Separate(new_left, new_right) => {
+                let approach = (right.velocity.0 - left.velocity.0).dot(normal);
+                let separation = (new_left.velocity.0 - new_right.velocity.0).dot(normal);
+                assert!((separation / approach - 0.5).abs() < 1e-12);
+            }
+            other => panic!("expected a bounce but got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn separating_bodies_do_not_bounce() {
+        let (left, right) = head_on();
+        let (left, right) = (
+            Body {
+                velocity: Velocity(left.velocity.0 * -1.0),
+                ..left
+            },
+            Body {
+                velocity: Velocity(right.velocity.0 * -1.0),
+                ..right
+            },
+        );
+
+        assert_eq!(
+            Bounce { restitution: 1.0 }.resolve(&left, &right),
+            Outcome::Separate(left, right)
+        );
+    }
+
+    #[test]
+    fn passing_through_changes_nothing() {
+        let (left, right) = head_on();
+        let outcome = PassThrough.resolve(&left, &right);
+
+        assert_momentum_conserved(outcome, &left, &right);
+        assert_eq!(outcome, Outcome::Separate(left, right));
+    }
 }
@@ -0,0 +1,253 @@
+use serde::{Deserialize, Serialize};
+
+use crate::engine::universe::Body;
+use crate::physics::force_solvers::ForceSolver;
+use crate::physics::gravity::Gravity;
+use crate::physics::primitives::{Force, Mass, Position, Scalar, Vector2D};
+
+/// Beyond this depth, bodies that share a cell are kept together rather than subdividing further.
+/// This stops coincident bodies from recursing forever.
+const MAX_DEPTH: usize = 64;
+
+/// Approximates the force on each body by treating distant groups of bodies as a single mass at
+/// their centre of mass. Runs in O(n log n).
+///
+/// A cell of width `s` at distance `d` from a body is aggregated when `s / d < opening_angle`. An
+/// opening angle of zero always opens every cell, which reduces to direct summation.
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+pub struct BarnesHut {
+    pub opening_angle: Scalar,
+}
+
+impl ForceSolver for BarnesHut {
+    fn forces(&self, gravity: &Gravity, bodies: &[Body]) -> Vec<Force> {
+        let mut root = match Cell::enclosing(bodies) {
+            Some(root) => root,
+            None => return Vec::new(),
+        };
+
+        for index in 0..bodies.len() {
+            root.insert(bodies, index, 0);
+        }
+
+        bodies
+            .iter()
+            .enumerate()
+            .map(|(index, body)| root.force_on(gravity, bodies, index, body, self.opening_angle))
+            .collect()
+    }
+}
+
+enum Contents {
+    Empty,
+    Bodies(Vec<usize>),
+    Quadrants(Box<[Cell; 4]>),
+}
+
+struct Cell {
+    centre: Vector2D,
+    half_width: Scalar,
+    mass: Mass,
+    mass_weighted_position: Vector2D,
+    contents: Contents,
+}
+
+impl Cell {
+    fn new(centre: Vector2D, half_width: Scalar) -> Cell {
+        Cell {
+            centre,
+            half_width,
+            mass: Mass(0.0),
+            mass_weighted_position: Vector2D::zero(),
+            contents: Contents::Empty,
+        }
+    }
+
+    fn enclosing(bodies: &[Body]) -> Option<Cell> {
+        let first = bodies.first()?.position.0;
+
+        let (min, max) = bodies.iter().fold((first, first), |(min, max), body| {
+            let position = body.position.0;
+            (
+                Vector2D::new(min.x.min(position.x), min.y.min(position.y)),
+                Vector2D::new(max.x.max(position.x), max.y.max(position.y)),
+            )
+        });
+
+        let centre = (min + max) / 2.0;
+        let half_width = Scalar::max(max.x - min.x, max.y - min.y) / 2.0;
+
+        // Pad the root slightly so that bodies on the boundary fall unambiguously inside it
+        Some(Cell::new(centre, half_width * 1.01 + Scalar::EPSILON))
+    }
+
+    fn centre_of_mass(&self) -> Position {
+        Position(self.mass_weighted_position / self.mass.0)
+    }
+
+    fn contains(&self, position: Position) -> bool {
+        (position.0.x - self.centre.x).abs() <= self.half_width
+            && (position.0.y - self.centre.y).abs() <= self.half_width
+    }
+
+    fn quadrant_index(&self, position: Position) -> usize {
+        let east = position.0.x >= self.centre.x;
+        let north = position.0.y >= self.centre.y;
+
+        match (east, north) {
+            (false, false) => 0,
+            (true, false) => 1,
+            (false, true) => 2,
+            (true, true) => 3,
+        }
+    }
+
+    fn quadrants(&self) -> Box<[Cell; 4]> {
+        let quarter_width = self.half_width / 2.0;
+        let quadrant = |dx: Scalar, dy: Scalar| {
+            Cell::new(
+                self.centre + Vector2D::new(dx, dy) * quarter_width,
+                quarter_width,
+            )
+        };
+
+        Box::new([
+            quadrant(-1.0, -1.0),
+            quadrant(1.0, -1.0),
+            quadrant(-1.0, 1.0),
+            quadrant(1.0, 1.0),
+        ])
+    }
+
+    fn insert(&mut self, bodies: &[Body], index: usize, depth: usize) {
+        let body = &bodies[index];
+
+        self.mass = self.mass + body.mass;
+        self.mass_weighted_position = self.mass_weighted_position + body.mass.0 * body.position.0;
+
+        self.contents = match std::mem::replace(&mut self.contents, Contents::Empty) {
+            Contents::Empty => Contents::Bodies(vec![index]),
+            Contents::Bodies(mut indexes) if depth >= MAX_DEPTH => {
+                indexes.push(index);
+                Contents::Bodies(indexes)
+            }
+            Contents::Bodies(indexes) => {
+                let mut quadrants = self.quadrants();
+
+                for existing_index in indexes.into_iter().chain(std::iter::once(index)) {
+                    let quadrant_index = self.quadrant_index(bodies[existing_index].position);
+                    quadrants[quadrant_index].insert(bodies, existing_index, depth + 1);
+                }
+
+                Contents::Quadrants(quadrants)
+            }
+            Contents::Quadrants(mut quadrants) => {
+                let quadrant_index = self.quadrant_index(body.position);
+                quadrants[quadrant_index].insert(bodies, index, depth + 1);
+                Contents::Quadrants(quadrants)
+            }
+        };
+    }
+
+    fn force_on(
+        &self,
+        gravity: &Gravity,
+        bodies: &[Body],
+        object_index: usize,
+        object: &Body,
+        opening_angle: Scalar,
+    ) -> Force {
+        match &self.contents {
+            Contents::Empty => Force(Vector2D::zero()),
+            Contents::Bodies(indexes) => indexes
+                .iter()
+                .filter(|subject_index| **subject_index != object_index)
+                .fold(Force(Vector2D::zero()), |total_force, subject_index| {
+                    total_force + gravity.due_to_bodies(object, &bodies[*subject_index])
+                }),
+            Contents::Quadrants(quadrants) => {
+                let centre_of_mass = self.centre_of_mass();
+                let distance = (centre_of_mass - object.position).0.magnitude();
+                let width = 2.0 * self.half_width;
+
+                if !self.contains(object.position) && width < opening_angle * distance {
+                    gravity.due_to(object.position, object.mass, centre_of_mass, self.mass)
+                } else {
+                    quadrants
+                        .iter()
+                        .filter(|quadrant| quadrant.mass.0 > 0.0)
+                        .fold(Force(Vector2D::zero()), |total_force, quadrant| {
+                            total_force
+                                + quadrant.force_on(
+                                    gravity,
+                                    bodies,
+                                    object_index,
+                                    object,
+                                    opening_angle,
+                                )
+                        })
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use crate::engine::universe::{Body, BodyId};
+    use crate::physics::force_solvers::{BarnesHut, DirectSummation, ForceSolver};
+    use crate::physics::gravity::Gravity;
+    use crate::physics::primitives::{Mass, Position, Vector2D, Velocity};
+
+    fn random_bodies(n: usize) -> Vec<Body> {
+        let mut rng = StdRng::seed_from_u64(42);
+
+        (0..n)
+            .map(|index| Body {
+                id: BodyId(index as u64),
+                mass: Mass(rng.gen_range(1e20..1e21)),
+                position: Position(Vector2D::new(
+                    rng.gen_range(-1e8..1e8),
+                    rng.gen_range(-1e8..1e8),
+                )),
+                velocity: Velocity(Vector2D::zero()),
+                radius: 0.0,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn barnes_hut_matches_direct_summation_within_tolerance() {
+        let bodies = random_bodies(500);
+
+        let direct = DirectSummation.forces(&Gravity::UNIVERSAL, &bodies);
+        let approximate = BarnesHut { opening_angle: 0.5 }.forces(&Gravity::UNIVERSAL, &bodies);
+
+        // Individual forces can nearly cancel, so compare the error across all bodies at once
+        let squared_error: f64 = direct
+            .iter()
+            .zip(approximate.iter())
+            .map(|(exact, approximation)| (exact.0 - approximation.0).magnitude().powi(2))
+            .sum();
+        let squared_magnitude: f64 = direct.iter().map(|force| force.0.magnitude().powi(2)).sum();
+
+        let relative_error = (squared_error / squared_magnitude).sqrt();
+        assert!(relative_error < 1e-2, "relative error {}", relative_error);
+    }
+
+    #[test]
+    fn barnes_hut_with_zero_opening_angle_is_exact() {
+        let bodies = random_bodies(100);
+
+        let direct = DirectSummation.forces(&Gravity::UNIVERSAL, &bodies);
+        let approximate = BarnesHut { opening_angle: 0.0 }.forces(&Gravity::UNIVERSAL, &bodies);
+
+        for (exact, approximation) in direct.iter().zip(approximate.iter()) {
+            let relative_error = (exact.0 - approximation.0).magnitude() / exact.0.magnitude();
+            assert!(relative_error < 1e-12, "relative error {}", relative_error);
+        }
+    }
+}
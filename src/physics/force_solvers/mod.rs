@@ -0,0 +1,53 @@
+use serde::{Deserialize, Serialize};
+
+use crate::engine::universe::Body;
+use crate::physics::gravity::Gravity;
+use crate::physics::primitives::{Force, Vector2D};
+
+pub use barnes_hut::BarnesHut;
+
+pub mod barnes_hut;
+
+/// Computes the total gravitational force acting on each of a set of bodies.
+pub trait ForceSolver: Sync {
+    /// Returns the net force on each body, in the same order as `bodies`.
+    fn forces(&self, gravity: &Gravity, bodies: &[Body]) -> Vec<Force>;
+}
+
+/// Sums the force between every pair of bodies. Exact, but O(n²).
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+pub struct DirectSummation;
+
+impl ForceSolver for DirectSummation {
+    fn forces(&self, gravity: &Gravity, bodies: &[Body]) -> Vec<Force> {
+        bodies
+            .iter()
+            .enumerate()
+            .map(|(object_index, object)| {
+                bodies
+                    .iter()
+                    .enumerate()
+                    .filter(|(subject_index, _)| *subject_index != object_index)
+                    .fold(Force(Vector2D::zero()), |total_force, (_, subject)| {
+                        total_force + gravity.due_to_bodies(object, subject)
+                    })
+            })
+            .collect()
+    }
+}
+
+/// Either force solver, chosen at runtime.
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+pub enum AnyForceSolver {
+    DirectSummation(DirectSummation),
+    BarnesHut(BarnesHut),
+}
+
+impl ForceSolver for AnyForceSolver {
+    fn forces(&self, gravity: &Gravity, bodies: &[Body]) -> Vec<Force> {
+        match self {
+            AnyForceSolver::DirectSummation(solver) => solver.forces(gravity, bodies),
+            AnyForceSolver::BarnesHut(solver) => solver.forces(gravity, bodies),
+        }
+    }
+}
@@ -1,20 +1,91 @@
-use crate::physics::primitives::{Force, Mass, Position, Scalar, Vector2D};
+use serde::{Deserialize, Serialize};
 
-#[derive(Copy, Clone)]
+use crate::physics::primitives::{Energy, Force, Mass, Position, Scalar, Vector2D};
+
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
 pub struct GravitationalConstant(Scalar);
 
 impl GravitationalConstant {
     pub const UNIVERSAL: GravitationalConstant = GravitationalConstant(6.67430e-11);
 }
 
-#[derive(Copy, Clone)]
+/// How the gravitational interaction is modified at short range, so that close approaches do not
+/// produce unbounded forces.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Softening {
+    /// Pure Newtonian gravity, which is singular when two bodies coincide.
+    None,
+    /// Plummer softening, which treats each body as a Plummer sphere of scale `length`.
+    Plummer { length: Scalar },
+    /// Cubic spline kernel softening. Exactly Newtonian beyond 2.8 × `length`, and with the same
+    /// central potential as Plummer softening of the same `length`.
+    Spline { length: Scalar },
+}
+
+/// Ratio of the spline kernel's support radius to the equivalent Plummer softening length
+const SPLINE_KERNEL_SCALE: Scalar = 2.8;
+
+impl Softening {
+    /// The softened equivalent of `1 / r`, such that the potential is `-G m₁ m₂ × this`
+    fn potential_kernel(self: Softening, distance: Scalar) -> Scalar {
+        match self {
+            Softening::None => 1.0 / distance,
+            Softening::Plummer { length } => 1.0 / (distance.powi(2) + length.powi(2)).sqrt(),
+            Softening::Spline { length } => {
+                let h = SPLINE_KERNEL_SCALE * length;
+                let u = distance / h;
+
+                if u < 0.5 {
+                    -(16.0 / 3.0 * u.powi(2) - 48.0 / 5.0 * u.powi(4) + 32.0 / 5.0 * u.powi(5)
+                        - 14.0 / 5.0)
+                        / h
+                } else if u < 1.0 {
+                    -(1.0 / (15.0 * u) + 32.0 / 3.0 * u.powi(2) - 16.0 * u.powi(3)
+                        + 48.0 / 5.0 * u.powi(4)
+                        - 32.0 / 15.0 * u.powi(5)
+                        - 16.0 / 5.0)
+                        / h
+                } else {
+                    1.0 / distance
+                }
+            }
+        }
+    }
+
+    /// The softened equivalent of `1 / r³`, such that the force is `G m₁ m₂ r⃗ × this`
+    fn force_kernel(self: Softening, distance: Scalar) -> Scalar {
+        match self {
+            Softening::None => 1.0 / distance.powi(3),
+            Softening::Plummer { length } => (distance.powi(2) + length.powi(2)).powf(-3.0 / 2.0),
+            Softening::Spline { length } => {
+                let h = SPLINE_KERNEL_SCALE * length;
+                let u = distance / h;
+
+                if u < 0.5 {
+                    (32.0 / 3.0 - 192.0 / 5.0 * u.powi(2) + 32.0 * u.powi(3)) / h.powi(3)
+                } else if u < 1.0 {
+                    (64.0 / 3.0 - 48.0 * u + 192.0 / 5.0 * u.powi(2)
+                        - 32.0 / 3.0 * u.powi(3)
+                        - 1.0 / (15.0 * u.powi(3)))
+                        / h.powi(3)
+                } else {
+                    1.0 / distance.powi(3)
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
 pub struct Gravity {
     pub gravitational_constant: GravitationalConstant,
+    pub softening: Softening,
 }
 
 impl Gravity {
     pub const UNIVERSAL: Gravity = Gravity {
         gravitational_constant: GravitationalConstant::UNIVERSAL,
+        softening: Softening::None,
     };
 
     pub fn due_to(
@@ -27,10 +98,119 @@ impl Gravity {
         let position_difference: Vector2D = subject_position.0 - object_position.0;
         let distance = position_difference.magnitude();
 
-        let force_magnitude =
-            (self.gravitational_constant.0 * object_mass.0 * subject_mass.0) / distance.powi(2);
-        let force_direction = position_difference.unit();
+        let force_per_unit_displacement = self.gravitational_constant.0
+            * object_mass.0
+            * subject_mass.0
+            * self.softening.force_kernel(distance);
+
+        Force(position_difference * force_per_unit_displacement)
+    }
+
+    /// The standard gravitational parameter `μ = G M` of a body, ignoring softening.
+    pub fn standard_gravitational_parameter(self: Gravity, mass: Mass) -> Scalar {
+        self.gravitational_constant.0 * mass.0
+    }
+
+    /// The potential energy of a pair of bodies, consistent with the force from `due_to`.
+    pub fn potential_energy(
+        self: Gravity,
+        object_position: Position,
+        object_mass: Mass,
+        subject_position: Position,
+        subject_mass: Mass,
+    ) -> Energy {
+        let distance = (subject_position.0 - object_position.0).magnitude();
+
+        Energy(
+            -self.gravitational_constant.0
+                * object_mass.0
+                * subject_mass.0
+                * self.softening.potential_kernel(distance),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::physics::gravity::{Gravity, Softening};
+    use crate::physics::primitives::{Mass, Position, Scalar, Vector2D};
+
+    const SOFTENINGS: [Softening; 2] = [
+        Softening::Plummer { length: 1e6 },
+        Softening::Spline { length: 1e6 },
+    ];
+
+    fn gravity_with(softening: Softening) -> Gravity {
+        Gravity {
+            softening,
+            ..Gravity::UNIVERSAL
+        }
+    }
+
+    fn force_at(gravity: Gravity, distance: Scalar) -> Vector2D {
+        gravity
+            .due_to(
+                Position(Vector2D::zero()),
+                Mass(1e22),
+                Position(Vector2D::new(distance, 0.0)),
+                Mass(1e20),
+            )
+            .0
+    }
+
+    fn potential_at(gravity: Gravity, distance: Scalar) -> Scalar {
+        gravity
+            .potential_energy(
+                Position(Vector2D::zero()),
+                Mass(1e22),
+                Position(Vector2D::new(distance, 0.0)),
+                Mass(1e20),
+            )
+            .0
+    }
+
+    #[test]
+    fn softened_force_is_finite_for_coincident_bodies() {
+        for softening in SOFTENINGS {
+            let gravity = gravity_with(softening);
+
+            assert_eq!(force_at(gravity, 0.0), Vector2D::zero());
+            assert!(potential_at(gravity, 0.0).is_finite());
+        }
+    }
+
+    #[test]
+    fn softened_force_is_newtonian_at_large_separation() {
+        let newtonian = force_at(Gravity::UNIVERSAL, 1e9);
+
+        for softening in SOFTENINGS {
+            let softened = force_at(gravity_with(softening), 1e9);
+            assert!((softened - newtonian).magnitude() / newtonian.magnitude() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn force_is_the_gradient_of_the_potential() {
+        for softening in [Softening::None].iter().chain(SOFTENINGS.iter()) {
+            let gravity = gravity_with(*softening);
+
+            for distance in [3e5, 1e6, 2e6, 2.7e6, 5e6] {
+                let delta = distance * 1e-6;
+                let gradient = (potential_at(gravity, distance + delta)
+                    - potential_at(gravity, distance - delta))
+                    / (2.0 * delta);
 
-        Force(force_direction * force_magnitude)
+                // The object is at the origin, so moving it along +x shrinks the separation
+                let force = force_at(gravity, distance);
+                assert!(
+                    (force.x - gradient).abs() / gradient.abs() < 1e-6,
+                    "{:?} at {}: force {} but gradient {}",
+                    softening,
+                    distance,
+                    force.x,
+                    gradient
+                );
+            }
+        }
     }
 }
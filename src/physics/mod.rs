@@ -1,6 +1,6 @@
-use crate::physics::primitives::*;
-
 pub mod collision;
+pub mod force_solvers;
 pub mod gravity;
 pub mod numerical_methods;
+pub mod orbit;
 pub mod primitives;
@@ -0,0 +1,345 @@
+use serde::{Deserialize, Serialize};
+
+use crate::physics::numerical_methods::{Components, OdeAlgorithm, OdeState, SubSteps};
+use crate::physics::primitives::Scalar;
+
+const C: [Scalar; 7] = [0.0, 1.0 / 5.0, 3.0 / 10.0, 4.0 / 5.0, 8.0 / 9.0, 1.0, 1.0];
+
+const A: [[Scalar; 6]; 7] = [
+    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [1.0 / 5.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [3.0 / 40.0, 9.0 / 40.0, 0.0, 0.0, 0.0, 0.0],
+    [44.0 / 45.0, -56.0 / 15.0, 32.0 / 9.0, 0.0, 0.0, 0.0],
+    [
+        19372.0 / 6561.0,
+        -25360.0 / 2187.0,
+        64448.0 / 6561.0,
+        -212.0 / 729.0,
+        0.0,
+        0.0,
+    ],
+    [
+        9017.0 / 3168.0,
+        -355.0 / 33.0,
+        46732.0 / 5247.0,
+        49.0 / 176.0,
+        -5103.0 / 18656.0,
+        0.0,
+    ],
+    [
+        35.0 / 384.0,
+        0.0,
+        500.0 / 1113.0,
+        125.0 / 192.0,
+        -2187.0 / 6784.0,
+        11.0 / 84.0,
+    ],
+];
+
+/// Weights of the fifth-order solution, which is the one propagated
+const B: [Scalar; 7] = [
+    35.0 / 384.0,
+    0.0,
+    500.0 / 1113.0,
+    125.0 / 192.0,
+    -2187.0 / 6784.0,
+    11.0 / 84.0,
+    0.0,
+];
+
+/// Difference between the fifth- and embedded fourth-order weights, giving the error estimate
+const E: [Scalar; 7] = [
+    71.0 / 57600.0,
+    0.0,
+    -71.0 / 16695.0,
+    71.0 / 1920.0,
+    -17253.0 / 339200.0,
+    22.0 / 525.0,
+    -1.0 / 40.0,
+];
+
+const SAFETY_FACTOR: Scalar = 0.9;
+const MIN_SCALE_FACTOR: Scalar = 0.2;
+const MAX_SCALE_FACTOR: Scalar = 5.0;
+
+/// A sub-step this much shorter than the requested step that still misses the tolerances fails,
+/// so that a singularity cannot stall the simulation.
+const MIN_STEP_FRACTION: Scalar = 1e-9;
+
+/// The Dormand–Prince embedded Runge–Kutta 5(4) method with adaptive step size control.
+///
+/// Each requested step is covered by as many sub-steps as are needed to keep the estimated local
+/// error of every position below `position_tolerance + relative_tolerance * |x|`, and of every
+/// velocity below `velocity_tolerance + relative_tolerance * |v|`. Each step starts from the
+/// sub-step length the step before it settled on.
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+pub struct DormandPrince {
+    /// In metres.
+    pub position_tolerance: Scalar,
+    /// In metres per second.
+    pub velocity_tolerance: Scalar,
+    pub relative_tolerance: Scalar,
+}
+
+impl DormandPrince {
+    /// Takes a single sub-step of length `h`, returning the new state and its error estimate.
+    fn try_step<Y, F>(&self, y_prime: &F, y_0: &Y, t_0: Scalar, h: Scalar) -> (Y, Y)
+    where
+        Y: OdeState,
+        F: Fn(Scalar, Y) -> Y,
+    {
+        let mut k: Vec<Y> = Vec::with_capacity(7);
+
+        for stage in 0..7 {
+            let y_stage = weighted_sum(y_0.clone(), &k, &A[stage], h);
+            k.push(y_prime(t_0 + C[stage] * h, y_stage));
+        }
+
+        let y_1 = weighted_sum(y_0.clone(), &k, &B, h);
+        let error = weighted_sum(k[0].clone() * 0.0, &k, &E, h);
+
+        (y_1, error)
+    }
+
+    /// The root-mean-square error relative to the tolerances, where anything at most 1 is
+    /// acceptable.
+    fn error_norm<Y: Components>(&self, y_0: &Y, y_1: &Y, error: &Y) -> Scalar {
+        let scaled_errors: Vec<Scalar> = self
+            .scaled_errors(
+                y_0.position_components(),
+                y_1.position_components(),
+                error.position_components(),
+                self.position_tolerance,
+            )
+            .chain(self.scaled_errors(
+                y_0.velocity_components(),
+                y_1.velocity_components(),
+                error.velocity_components(),
+                self.velocity_tolerance,
+            ))
+            .collect();
+
+        if scaled_errors.is_empty() {
+            return 0.0;
+        }
+
+        (scaled_errors.iter().map(|e| e.powi(2)).sum::<Scalar>() / scaled_errors.len() as Scalar)
+            .sqrt()
+    }
+
+    fn scaled_errors(
+        &self,
+        before: Vec<Scalar>,
+        after: Vec<Scalar>,
+        errors: Vec<Scalar>,
+        absolute_tolerance: Scalar,
+    ) -> impl Iterator<Item = Scalar> {
+        let relative_tolerance = self.relative_tolerance;
+
+        before
+            .into_iter()
+            .zip(after)
+            .zip(errors)
+            .map(move |((before, after), error)| {
+                error / (absolute_tolerance + relative_tolerance * before.abs().max(after.abs()))
+            })
+    }
+}
+
+fn weighted_sum<Y: OdeState>(base: Y, k: &[Y], weights: &[Scalar], h: Scalar) -> Y {
+    k.iter()
+        .zip(weights.iter())
+        .filter(|(_, weight)| **weight != 0.0)
+        .fold(base, |sum, (k_i, weight)| sum + k_i.clone() * (weight * h))
+}
+
+impl<Y: OdeState + Components> OdeAlgorithm<Y, Scalar> for DormandPrince {
+    fn next_y<F>(&self, y_prime: F, y_0: Y, t_0: Scalar, h: Scalar) -> Y
+    where
+        F: Fn(Scalar, Y) -> Y,
+    {
+        self.next_y_with_sub_steps(y_prime, y_0, t_0, h, &SubSteps::default())
+            .0
+    }
+
+    fn next_y_with_sub_steps<F>(
+        &self,
+        y_prime: F,
+        y_0: Y,
+        t_0: Scalar,
+        h: Scalar,
+        previous: &SubSteps,
+    ) -> (Y, SubSteps)
+    where
+        F: Fn(Scalar, Y) -> Y,
+    {
+        let mut sub_steps = SubSteps::default();
+
+        let mut y = y_0;
+        let mut t = t_0;
+        let mut remaining = h;
+        // Takes the direction of this step, which may differ from the one before
+        let mut sub_step = match previous.next_sub_step {
+            Some(length) if length != 0.0 => length.abs().copysign(h),
+            _ => h,
+        };
+
+        while remaining != 0.0 {
+            // Never step past the end of the requested step. Works for negative steps too.
+            let shortened = sub_step.abs() > remaining.abs();
+            let this_sub_step = if shortened { remaining } else { sub_step };
+
+            let (y_next, error) = self.try_step(&y_prime, &y, t, this_sub_step);
+            let error_norm = self.error_norm(&y, &y_next, &error);
+
+            if error_norm <= 1.0 {
+                sub_steps.accepted += 1;
+
+                y = y_next;
+                t += this_sub_step;
+                remaining -= this_sub_step;
+
+                // A shortened sub-step says nothing about how long the next one could be
+                if shortened {
+                    continue;
+                }
+            } else if error_norm.is_nan() || this_sub_step.abs() <= MIN_STEP_FRACTION * h.abs() {
+                // The state has broken down, or is changing too abruptly to follow. Either way
+                // nothing after this point can be trusted, so leave the rest of the step undone.
+                sub_steps.failed += 1;
+                sub_steps.unfinished = remaining;
+                break;
+            } else {
+                sub_steps.rejected += 1;
+            }
+
+            let scale_factor = if error_norm == 0.0 {
+                MAX_SCALE_FACTOR
+            } else {
+                (SAFETY_FACTOR * error_norm.powf(-1.0 / 5.0))
+                    .clamp(MIN_SCALE_FACTOR, MAX_SCALE_FACTOR)
+            };
+
+            sub_step = this_sub_step * scale_factor;
+        }
+
+        sub_steps.next_sub_step = Some(sub_step);
+
+        (y, sub_steps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::physics::numerical_methods::{DormandPrince, OdeAlgorithm, PhaseSpace, SubSteps};
+    use crate::physics::primitives::{Scalar, Vector2D};
+
+    /// A body on a spring fixed at the origin
+    fn oscillator(_: Scalar, state: PhaseSpace) -> PhaseSpace {
+        PhaseSpace {
+            positions: state.velocities.clone(),
+            velocities: state.positions.iter().map(|x| *x * -1.0).collect(),
+        }
+    }
+
+    fn at_rest(x: Scalar, y: Scalar) -> PhaseSpace {
+        PhaseSpace {
+            positions: vec![Vector2D::new(x, y)],
+            velocities: vec![Vector2D::zero()],
+        }
+    }
+
+    fn distance(left: &PhaseSpace, right: &PhaseSpace) -> Scalar {
+        (left.positions[0] - right.positions[0]).magnitude()
+            + (left.velocities[0] - right.velocities[0]).magnitude()
+    }
+
+    const PERIOD: Scalar = 2.0 * std::f64::consts::PI;
+
+    fn with_tolerance(tolerance: Scalar) -> DormandPrince {
+        DormandPrince {
+            position_tolerance: tolerance,
+            velocity_tolerance: tolerance,
+            relative_tolerance: tolerance,
+        }
+    }
+
+    #[test]
+    fn dormand_prince_sub_steps_within_requested_step() {
+        let (y, sub_steps) = with_tolerance(1e-10).next_y_with_sub_steps(
+            oscillator,
+            at_rest(1.0, 0.0),
+            0.0,
+            PERIOD,
+            &SubSteps::default(),
+        );
+
+        assert!(distance(&y, &at_rest(1.0, 0.0)) < 1e-8, "{:?}", y);
+        assert!(sub_steps.accepted > 1);
+        assert!(sub_steps.rejected > 0);
+        assert_eq!(sub_steps.failed, 0);
+    }
+
+    #[test]
+    fn dormand_prince_takes_more_steps_for_tighter_tolerances() {
+        let steps_for_tolerance = |tolerance: Scalar| {
+            with_tolerance(tolerance)
+                .next_y_with_sub_steps(
+                    oscillator,
+                    at_rest(1.0, 0.0),
+                    0.0,
+                    PERIOD,
+                    &SubSteps::default(),
+                )
+                .1
+                .accepted
+        };
+
+        assert!(steps_for_tolerance(1e-10) > steps_for_tolerance(1e-4));
+    }
+
+    #[test]
+    fn dormand_prince_carries_its_sub_step_on_to_the_next_step() {
+        let algorithm = with_tolerance(1e-10);
+
+        let (y, first) = algorithm.next_y_with_sub_steps(
+            oscillator,
+            at_rest(1.0, 0.0),
+            0.0,
+            PERIOD / 4.0,
+            &SubSteps::default(),
+        );
+        let (_, second) =
+            algorithm.next_y_with_sub_steps(oscillator, y, PERIOD / 4.0, PERIOD / 4.0, &first);
+
+        assert!(first.rejected > 0);
+        assert_eq!(second.rejected, 0);
+    }
+
+    #[test]
+    fn dormand_prince_integrates_backwards() {
+        let y = with_tolerance(1e-10).next_y(oscillator, at_rest(1.0, 0.0), 0.0, -PERIOD / 4.0);
+
+        let expected = PhaseSpace {
+            positions: vec![Vector2D::zero()],
+            velocities: vec![Vector2D::new(1.0, 0.0)],
+        };
+        assert!(distance(&y, &expected) < 1e-8, "{:?}", y);
+    }
+
+    #[test]
+    fn dormand_prince_fails_rather_than_carrying_on_from_a_broken_state() {
+        let (y, sub_steps) = with_tolerance(1e-10).next_y_with_sub_steps(
+            |_, state: PhaseSpace| state * Scalar::NAN,
+            at_rest(1.0, 0.0),
+            0.0,
+            1.0,
+            &SubSteps::default(),
+        );
+
+        assert_eq!(y, at_rest(1.0, 0.0));
+        assert_eq!(sub_steps.accepted, 0);
+        assert_eq!(sub_steps.failed, 1);
+        assert_eq!(sub_steps.unfinished, 1.0);
+    }
+}
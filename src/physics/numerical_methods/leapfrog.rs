@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+
+use crate::physics::numerical_methods::{OdeAlgorithm, PhaseSpace};
+use crate::physics::primitives::Scalar;
+
+/// The kick-drift-kick leapfrog method, equivalent to velocity Verlet.
+///
+/// Second order and symplectic, so energy errors stay bounded over long runs rather than drifting.
+/// It is also time-reversible. Accelerations must depend only on positions.
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+pub struct Leapfrog;
+
+impl OdeAlgorithm<PhaseSpace, Scalar> for Leapfrog {
+    fn next_y<F>(&self, y_prime: F, y_0: PhaseSpace, t_0: Scalar, h: Scalar) -> PhaseSpace
+    where
+        F: Fn(Scalar, PhaseSpace) -> PhaseSpace,
+    {
+        let initial_accelerations = y_prime(t_0, y_0.clone()).velocities;
+
+        let half_step_velocities: Vec<_> = y_0
+            .velocities
+            .iter()
+            .zip(initial_accelerations)
+            .map(|(velocity, acceleration)| *velocity + acceleration * (h / 2.0))
+            .collect();
+
+        let positions: Vec<_> = y_0
+            .positions
+            .iter()
+            .zip(half_step_velocities.iter())
+            .map(|(position, velocity)| *position + *velocity * h)
+            .collect();
+
+        let final_accelerations = y_prime(
+            t_0 + h,
+            PhaseSpace {
+                positions: positions.clone(),
+                velocities: half_step_velocities.clone(),
+            },
+        )
+        .velocities;
+
+        let velocities = half_step_velocities
+            .into_iter()
+            .zip(final_accelerations)
+            .map(|(velocity, acceleration)| velocity + acceleration * (h / 2.0))
+            .collect();
+
+        PhaseSpace {
+            positions,
+            velocities,
+        }
+    }
+
+    fn is_time_reversible(&self) -> bool {
+        true
+    }
+}
@@ -1,20 +1,169 @@
-use crate::physics::primitives::{Scalar, Vector2D};
+use serde::{Deserialize, Serialize};
 
-pub mod euler_method;
+use std::ops::{Add, Mul};
+
+use crate::physics::primitives::Scalar;
+
+pub use dormand_prince::DormandPrince;
+pub use leapfrog::Leapfrog;
+pub use phase_space::PhaseSpace;
+pub use runge_kutta::RungeKutta4;
+
+pub mod dormand_prince;
+pub mod leapfrog;
+pub mod phase_space;
+pub mod runge_kutta;
 
 pub trait OdeAlgorithm<Y, T>: Sync {
-    fn next_y<F>(self: &Self, y_prime: F, y_0: Y, t_0: T, h: T) -> Y
+    fn next_y<F>(&self, y_prime: F, y_0: Y, t_0: T, h: T) -> Y
     where
         F: Fn(T, Y) -> Y;
+
+    /// As `next_y`, but also reports how many internal sub-steps were needed to cover `h`.
+    /// `previous` is what was reported for the step before, which adaptive algorithms carry their
+    /// sub-step length on from. Fixed-step algorithms always take a single accepted step.
+    fn next_y_with_sub_steps<F>(
+        &self,
+        y_prime: F,
+        y_0: Y,
+        t_0: T,
+        h: T,
+        _previous: &SubSteps,
+    ) -> (Y, SubSteps)
+    where
+        F: Fn(T, Y) -> Y,
+    {
+        (
+            self.next_y(y_prime, y_0, t_0, h),
+            SubSteps {
+                accepted: 1,
+                ..SubSteps::default()
+            },
+        )
+    }
+
+    /// Whether a step of `-h` undoes a step of `h`, up to rounding, so that running with a
+    /// negative step retraces the path already taken.
+    fn is_time_reversible(&self) -> bool {
+        false
+    }
 }
 
+/// The number of sub-steps an algorithm took to advance by one requested step.
+#[derive(Debug, PartialEq, Copy, Clone, Default, Serialize, Deserialize)]
+pub struct SubSteps {
+    pub accepted: u32,
+    pub rejected: u32,
+    /// Sub-steps whose error could not be brought within tolerance, either because the state had
+    /// broken down or because even the shortest sub-step allowed missed it. The rest of the step
+    /// is then left undone, so the result cannot be trusted.
+    pub failed: u32,
+    /// How much of the requested step was left undone after a failed sub-step. The state reached
+    /// is the one at the start of the step plus the rest.
+    #[serde(default)]
+    pub unfinished: Scalar,
+    /// The sub-step length an adaptive algorithm settled on, which the next step starts from.
+    pub next_sub_step: Option<Scalar>,
+}
+
+/// A state that general purpose algorithms can integrate, ie one that can be summed and scaled.
+pub trait OdeState: Clone + Add<Output = Self> + Mul<Scalar, Output = Self> {}
+
+impl<Y: Clone + Add<Output = Y> + Mul<Scalar, Output = Y>> OdeState for Y {}
+
+/// A state whose individual components can be inspected, which adaptive algorithms need in order
+/// to estimate their error. Positions and velocities are kept apart because they are measured in
+/// different units, and so need tolerances of their own.
+pub trait Components {
+    fn position_components(&self) -> Vec<Scalar>;
+    fn velocity_components(&self) -> Vec<Scalar>;
+}
+
+impl Components for PhaseSpace {
+    fn position_components(&self) -> Vec<Scalar> {
+        self.positions
+            .iter()
+            .flat_map(|position| [position.x, position.y])
+            .collect()
+    }
+
+    fn velocity_components(&self) -> Vec<Scalar> {
+        self.velocities
+            .iter()
+            .flat_map(|velocity| [velocity.x, velocity.y])
+            .collect()
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
 pub struct EulerMethod;
 
-impl OdeAlgorithm<Vector2D, Scalar> for EulerMethod {
-    fn next_y<F>(self: &Self, y_prime: F, y_0: Vector2D, t_0: Scalar, h: f64) -> Vector2D
+impl<Y: OdeState> OdeAlgorithm<Y, Scalar> for EulerMethod {
+    fn next_y<F>(&self, y_prime: F, y_0: Y, t_0: Scalar, h: f64) -> Y
+    where
+        F: Fn(Scalar, Y) -> Y,
+    {
+        y_0.clone() + (y_prime(t_0, y_0) * h)
+    }
+}
+
+/// Any one of the numerical methods for integrating a `PhaseSpace`, chosen at runtime.
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+pub enum AnyNumericalMethod {
+    Euler(EulerMethod),
+    Leapfrog(Leapfrog),
+    RungeKutta4(RungeKutta4),
+    DormandPrince(DormandPrince),
+}
+
+impl OdeAlgorithm<PhaseSpace, Scalar> for AnyNumericalMethod {
+    fn next_y<F>(&self, y_prime: F, y_0: PhaseSpace, t_0: Scalar, h: Scalar) -> PhaseSpace
+    where
+        F: Fn(Scalar, PhaseSpace) -> PhaseSpace,
+    {
+        self.next_y_with_sub_steps(y_prime, y_0, t_0, h, &SubSteps::default())
+            .0
+    }
+
+    fn next_y_with_sub_steps<F>(
+        &self,
+        y_prime: F,
+        y_0: PhaseSpace,
+        t_0: Scalar,
+        h: Scalar,
+        previous: &SubSteps,
+    ) -> (PhaseSpace, SubSteps)
     where
-        F: Fn(Scalar, Vector2D) -> Vector2D,
+        F: Fn(Scalar, PhaseSpace) -> PhaseSpace,
     {
-        y_0 + (y_prime(t_0, y_0) * h)
+        match self {
+            AnyNumericalMethod::Euler(method) => {
+                method.next_y_with_sub_steps(y_prime, y_0, t_0, h, previous)
+            }
+            AnyNumericalMethod::Leapfrog(method) => {
+                method.next_y_with_sub_steps(y_prime, y_0, t_0, h, previous)
+            }
+            AnyNumericalMethod::RungeKutta4(method) => {
+                method.next_y_with_sub_steps(y_prime, y_0, t_0, h, previous)
+            }
+            AnyNumericalMethod::DormandPrince(method) => {
+                method.next_y_with_sub_steps(y_prime, y_0, t_0, h, previous)
+            }
+        }
+    }
+
+    fn is_time_reversible(&self) -> bool {
+        match self {
+            AnyNumericalMethod::Euler(method) => {
+                OdeAlgorithm::<PhaseSpace, Scalar>::is_time_reversible(method)
+            }
+            AnyNumericalMethod::Leapfrog(method) => method.is_time_reversible(),
+            AnyNumericalMethod::RungeKutta4(method) => {
+                OdeAlgorithm::<PhaseSpace, Scalar>::is_time_reversible(method)
+            }
+            AnyNumericalMethod::DormandPrince(method) => {
+                OdeAlgorithm::<PhaseSpace, Scalar>::is_time_reversible(method)
+            }
+        }
     }
 }
@@ -0,0 +1,53 @@
+use core::ops;
+
+use crate::physics::primitives::{Scalar, Vector2D};
+
+/// The positions and velocities of every body in a system, integrated together as a single state.
+///
+/// The time derivative of a `PhaseSpace` is also represented as a `PhaseSpace`, in which case
+/// `positions` holds the velocities and `velocities` holds the accelerations.
+#[derive(Debug, PartialEq, Clone)]
+pub struct PhaseSpace {
+    pub positions: Vec<Vector2D>,
+    pub velocities: Vec<Vector2D>,
+}
+
+impl PhaseSpace {
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+}
+
+fn add_all(left: Vec<Vector2D>, right: Vec<Vector2D>) -> Vec<Vector2D> {
+    left.into_iter().zip(right).map(|(l, r)| l + r).collect()
+}
+
+fn scale_all(vectors: Vec<Vector2D>, scalar: Scalar) -> Vec<Vector2D> {
+    vectors.into_iter().map(|v| v * scalar).collect()
+}
+
+impl ops::Add for PhaseSpace {
+    type Output = PhaseSpace;
+
+    fn add(self, rhs: PhaseSpace) -> Self::Output {
+        PhaseSpace {
+            positions: add_all(self.positions, rhs.positions),
+            velocities: add_all(self.velocities, rhs.velocities),
+        }
+    }
+}
+
+impl ops::Mul<Scalar> for PhaseSpace {
+    type Output = PhaseSpace;
+
+    fn mul(self, rhs: Scalar) -> Self::Output {
+        PhaseSpace {
+            positions: scale_all(self.positions, rhs),
+            velocities: scale_all(self.velocities, rhs),
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use serde::{Deserialize, Serialize};
+
+use crate::physics::numerical_methods::{OdeAlgorithm, OdeState};
+use crate::physics::primitives::Scalar;
+
+/// The classical fourth-order Runge–Kutta method.
+///
+/// Evaluates the derivative four times per step, at the start, twice at the midpoint and at the
+/// end, so the forces respond to where the bodies are part way through the step.
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+pub struct RungeKutta4;
+
+impl<Y: OdeState> OdeAlgorithm<Y, Scalar> for RungeKutta4 {
+    fn next_y<F>(&self, y_prime: F, y_0: Y, t_0: Scalar, h: Scalar) -> Y
+    where
+        F: Fn(Scalar, Y) -> Y,
+    {
+        let k_1 = y_prime(t_0, y_0.clone());
+        let k_2 = y_prime(t_0 + h / 2.0, y_0.clone() + k_1.clone() * (h / 2.0));
+        let k_3 = y_prime(t_0 + h / 2.0, y_0.clone() + k_2.clone() * (h / 2.0));
+        let k_4 = y_prime(t_0 + h, y_0.clone() + k_3.clone() * h);
+
+        y_0 + (k_1 + k_2 * 2.0 + k_3 * 2.0 + k_4) * (h / 6.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::universe::{Body, BodyId, Universe};
+    use crate::engine::Engine;
+    use crate::physics::collision::PassThrough;
+    use crate::physics::force_solvers::DirectSummation;
+    use crate::physics::gravity::Gravity;
+    use crate::physics::numerical_methods::{Leapfrog, OdeAlgorithm, PhaseSpace, RungeKutta4};
+    use crate::physics::primitives::{
+        Mass, Position, Scalar, TemporalDuration, Vector2D, Velocity,
+    };
+
+    const G: Scalar = 6.67430e-11;
+
+    const PRIMARY_MASS: Scalar = 1e24;
+    const SECONDARY_MASS: Scalar = 1e22;
+    const SEPARATION: Scalar = 1e8;
+
+    /// Two bodies on circular orbits about their common centre of mass
+    fn circular_orbit() -> Universe {
+        let total_mass = PRIMARY_MASS + SECONDARY_MASS;
+        let relative_speed = (G * total_mass / SEPARATION).sqrt();
+
+        let body = |mass: Scalar, offset: Scalar, speed: Scalar| Body {
+            id: BodyId::default(),
+            mass: Mass(mass),
+            position: Position(Vector2D::new(offset, 0.0)),
+            velocity: Velocity(Vector2D::new(0.0, speed)),
+            radius: 0.0,
+        };
+
+        Universe::new(
+            Gravity::UNIVERSAL,
+            vec![
+                body(
+                    PRIMARY_MASS,
+                    -SEPARATION * SECONDARY_MASS / total_mass,
+                    -relative_speed * SECONDARY_MASS / total_mass,
+                ),
+                body(
+                    SECONDARY_MASS,
+                    SEPARATION * PRIMARY_MASS / total_mass,
+                    relative_speed * PRIMARY_MASS / total_mass,
+                ),
+            ],
+        )
+    }
+
+    /// Integrates exactly one orbital period, returning how far the secondary is from where it
+    /// started
+    fn error_after_one_orbit<A: OdeAlgorithm<PhaseSpace, Scalar>>(
+        numerical_method: A,
+        steps: u32,
+    ) -> Scalar {
+        let engine = Engine {
+            numerical_method,
+            force_solver: DirectSummation,
+            collision_policy: PassThrough,
+        };
+
+        let period = 2.0
+            * std::f64::consts::PI
+            * (SEPARATION.powi(3) / (G * (PRIMARY_MASS + SECONDARY_MASS))).sqrt();
+        let dt = TemporalDuration(period / steps as Scalar);
+
+        let initial = circular_orbit();
+        let mut universe = circular_orbit();
+
+        for _ in 0..steps {
+            universe = engine.step_forward(&universe, dt);
+        }
+
+        (universe.bodies[1].position - initial.bodies[1].position)
+            .0
+            .magnitude()
+    }
+
+    #[test]
+    fn runge_kutta_4_converges_at_fourth_order() {
+        let coarse_error = error_after_one_orbit(RungeKutta4, 100);
+        let fine_error = error_after_one_orbit(RungeKutta4, 200);
+
+        // Halving the step should cut the error by 2⁴ = 16
+        let convergence_ratio = coarse_error / fine_error;
+        assert!(
+            convergence_ratio > 12.0 && convergence_ratio < 20.0,
+            "convergence ratio {}",
+            convergence_ratio
+        );
+    }
+
+    #[test]
+    fn runge_kutta_4_is_more_accurate_than_leapfrog() {
+        let runge_kutta_error = error_after_one_orbit(RungeKutta4, 200);
+        let leapfrog_error = error_after_one_orbit(Leapfrog, 200);
+
+        assert!(
+            runge_kutta_error * 10.0 < leapfrog_error,
+            "runge-kutta error {}, leapfrog error {}",
+            runge_kutta_error,
+            leapfrog_error
+        );
+    }
+}
@@ -0,0 +1,285 @@
+use std::f64::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+use crate::engine::universe::{Body, BodyId};
+use crate::physics::gravity::Gravity;
+use crate::physics::primitives::{Mass, Position, Scalar, TemporalDuration, Vector2D, Velocity};
+
+/// Which way round its parent a body travels.
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+pub enum OrbitDirection {
+    Anticlockwise,
+    Clockwise,
+}
+
+/// The Keplerian elements of a bound orbit in the plane, describing the shape of a body's orbit
+/// around its parent and where along it the body is. Angles are in radians.
+///
+/// Elements only describe the two-body problem, so they ignore softening and every other body.
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+pub struct OrbitalElements {
+    pub semi_major_axis: Scalar,
+    /// Zero for a circular orbit, approaching one as the orbit becomes a parabola.
+    pub eccentricity: Scalar,
+    /// The angle from the x axis to periapsis.
+    pub argument_of_periapsis: Scalar,
+    /// The fraction of the period since periapsis, as an angle.
+    pub mean_anomaly: Scalar,
+    pub direction: OrbitDirection,
+}
+
+/// Eccentricities below this are treated as circular, where periapsis is undefined.
+const CIRCULAR_ECCENTRICITY: Scalar = 1e-12;
+
+impl OrbitalElements {
+    /// A body of `mass` and `radius` on this orbit around `parent`, yet to be given an id by being
+    /// added to a universe.
+    pub fn body_orbiting(
+        self: &OrbitalElements,
+        gravity: &Gravity,
+        parent: &Body,
+        mass: Mass,
+        radius: Scalar,
+    ) -> Body {
+        let mu = gravity.standard_gravitational_parameter(parent.mass + mass);
+        let (a, e) = (self.semi_major_axis, self.eccentricity);
+
+        let eccentric_anomaly = eccentric_anomaly(self.mean_anomaly, e);
+        let (sin_e, cos_e) = eccentric_anomaly.sin_cos();
+        let mean_motion = (mu / a.powi(3)).sqrt();
+        let semi_minor_ratio = (1.0 - e.powi(2)).sqrt();
+        let rate_of_eccentric_anomaly = mean_motion / (1.0 - e * cos_e);
+
+        // In the frame with periapsis along the x axis
+        let position = Vector2D::new(a * (cos_e - e), a * semi_minor_ratio * sin_e);
+        let velocity = Vector2D::new(
+            -a * sin_e * rate_of_eccentric_anomaly,
+            a * semi_minor_ratio * cos_e * rate_of_eccentric_anomaly,
+        );
+
+        let to_parent_frame = |vector: Vector2D| {
+            rotate(
+                self.direction.mirror_if_clockwise(vector),
+                self.argument_of_periapsis,
+            )
+        };
+
+        Body {
+            id: BodyId::default(),
+            mass,
+            position: Position(parent.position.0 + to_parent_frame(position)),
+            velocity: Velocity(parent.velocity.0 + to_parent_frame(velocity)),
+            radius,
+        }
+    }
+
+    /// The elements of `body`'s orbit around `parent`, or `None` if the pair is not bound or
+    /// the two are in the same place.
+    pub fn of(gravity: &Gravity, body: &Body, parent: &Body) -> Option<OrbitalElements> {
+        let mu = gravity.standard_gravitational_parameter(parent.mass + body.mass);
+        let position = (body.position - parent.position).0;
+        let velocity = body.velocity.0 - parent.velocity.0;
+        let distance = position.magnitude();
+        if distance == 0.0 {
+            return None;
+        }
+
+        let specific_energy = velocity.magnitude().powi(2) / 2.0 - mu / distance;
+        if specific_energy.is_nan() || specific_energy >= 0.0 {
+            return None;
+        }
+
+        let semi_major_axis = -mu / (2.0 * specific_energy);
+        let direction = if position.cross(velocity) < 0.0 {
+            OrbitDirection::Clockwise
+        } else {
+            OrbitDirection::Anticlockwise
+        };
+
+        // Points from the parent towards periapsis
+        let eccentricity_vector = (position * (velocity.magnitude().powi(2) - mu / distance)
+            - velocity * position.dot(velocity))
+            / mu;
+        let eccentricity = eccentricity_vector.magnitude();
+
+        let argument_of_periapsis = if eccentricity < CIRCULAR_ECCENTRICITY {
+            0.0
+        } else {
+            eccentricity_vector.y.atan2(eccentricity_vector.x)
+        };
+
+        let position_from_periapsis =
+            direction.mirror_if_clockwise(rotate(position, -argument_of_periapsis));
+        let true_anomaly = position_from_periapsis.y.atan2(position_from_periapsis.x);
+
+        let eccentric_anomaly = ((1.0 - eccentricity.powi(2)).sqrt() * true_anomaly.sin())
+            .atan2(eccentricity + true_anomaly.cos());
+        let mean_anomaly = eccentric_anomaly - eccentricity * eccentric_anomaly.sin();
+
+        Some(OrbitalElements {
+            semi_major_axis,
+            eccentricity,
+            argument_of_periapsis: argument_of_periapsis.rem_euclid(2.0 * PI),
+            mean_anomaly: mean_anomaly.rem_euclid(2.0 * PI),
+            direction,
+        })
+    }
+
+    /// The time to complete one orbit, when the body and its parent have `total_mass` between them.
+    pub fn period(self: &OrbitalElements, gravity: &Gravity, total_mass: Mass) -> TemporalDuration {
+        let mu = gravity.standard_gravitational_parameter(total_mass);
+
+        TemporalDuration(2.0 * PI * (self.semi_major_axis.powi(3) / mu).sqrt())
+    }
+}
+
+impl OrbitDirection {
+    /// Clockwise orbits are mirror images of anticlockwise ones in the line to periapsis.
+    fn mirror_if_clockwise(self: OrbitDirection, vector: Vector2D) -> Vector2D {
+        match self {
+            OrbitDirection::Anticlockwise => vector,
+            OrbitDirection::Clockwise => Vector2D::new(vector.x, -vector.y),
+        }
+    }
+}
+
+/// Solves Kepler's equation `M = E - e sin E` for the eccentric anomaly `E`.
+fn eccentric_anomaly(mean_anomaly: Scalar, eccentricity: Scalar) -> Scalar {
+    const MAX_ITERATIONS: usize = 50;
+
+    let mean_anomaly = mean_anomaly.rem_euclid(2.0 * PI);
+    // Starting from π converges for every eccentricity, where M itself can overshoot
+    let mut eccentric_anomaly = if eccentricity > 0.8 { PI } else { mean_anomaly };
+
+    for _ in 0..MAX_ITERATIONS {
+        let correction =
+            (eccentric_anomaly - eccentricity * eccentric_anomaly.sin() - mean_anomaly)
+                / (1.0 - eccentricity * eccentric_anomaly.cos());
+        eccentric_anomaly -= correction;
+
+        if correction.abs() < 1e-15 {
+            break;
+        }
+    }
+
+    eccentric_anomaly
+}
+
+fn rotate(vector: Vector2D, angle: Scalar) -> Vector2D {
+    let (sin, cos) = angle.sin_cos();
+
+    Vector2D::new(
+        vector.x * cos - vector.y * sin,
+        vector.x * sin + vector.y * cos,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::universe::{Body, BodyId};
+    use crate::physics::gravity::Gravity;
+    use crate::physics::orbit::{OrbitDirection, OrbitalElements};
+    use crate::physics::primitives::{Mass, Position, Scalar, Vector2D, Velocity};
+
+    fn parent() -> Body {
+        Body {
+            id: BodyId(0),
+            mass: Mass(1e24),
+            position: Position(Vector2D::new(3e8, -2e8)),
+            velocity: Velocity(Vector2D::new(-40.0, 25.0)),
+            radius: 0.0,
+        }
+    }
+
+    fn assert_close(actual: Scalar, expected: Scalar, tolerance: Scalar) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "expected {} but was {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn periapsis_is_where_vis_viva_says() {
+        let gravity = Gravity::UNIVERSAL;
+        let elements = OrbitalElements {
+            semi_major_axis: 1e8,
+            eccentricity: 0.5,
+            argument_of_periapsis: std::f64::consts::FRAC_PI_2,
+            mean_anomaly: 0.0,
+            direction: OrbitDirection::Anticlockwise,
+        };
+
+        let body = elements.body_orbiting(&gravity, &parent(), Mass(1e22), 0.0);
+        let position = (body.position - parent().position).0;
+        let velocity = body.velocity.0 - parent().velocity.0;
+
+        // Periapsis is a(1 - e) along the y axis, moving anticlockwise
+        assert_close(position.x, 0.0, 1e-6);
+        assert_close(position.y, 0.5e8, 1e-6);
+
+        let mu = gravity.standard_gravitational_parameter(Mass(1e24 + 1e22));
+        let speed = (mu * (2.0 / 0.5e8 - 1.0 / 1e8)).sqrt();
+        assert_close(velocity.x, -speed, 1e-9);
+        assert_close(velocity.y, 0.0, 1e-9);
+    }
+
+    #[test]
+    fn elements_round_trip_through_state_vectors() {
+        let gravity = Gravity::UNIVERSAL;
+
+        for &eccentricity in &[0.0, 0.0002, 0.3, 0.95] {
+            for &direction in &[OrbitDirection::Anticlockwise, OrbitDirection::Clockwise] {
+                for &mean_anomaly in &[0.0, 1.0, 3.0, 5.5] {
+                    let elements = OrbitalElements {
+                        semi_major_axis: 2e7,
+                        eccentricity,
+                        argument_of_periapsis: if eccentricity == 0.0 { 0.0 } else { 2.0 },
+                        mean_anomaly,
+                        direction,
+                    };
+
+                    let body = elements.body_orbiting(&gravity, &parent(), Mass(1e21), 0.0);
+                    let recovered = OrbitalElements::of(&gravity, &body, &parent()).unwrap();
+
+                    assert_eq!(recovered.direction, direction);
+                    assert_close(recovered.semi_major_axis, 2e7, 1e-3);
+                    assert_close(recovered.eccentricity, eccentricity, 1e-9);
+                    if eccentricity > 0.0 {
+                        assert_close(recovered.argument_of_periapsis, 2.0, 1e-6);
+                    }
+                    let angle_difference = (recovered.mean_anomaly - mean_anomaly).sin();
+                    assert_close(angle_difference, 0.0, 1e-6);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn unbound_bodies_have_no_elements() {
+        let escaping = Body {
+            id: BodyId(1),
+            mass: Mass(1e21),
+            position: Position(parent().position.0 + Vector2D::new(1e7, 0.0)),
+            velocity: Velocity(parent().velocity.0 + Vector2D::new(0.0, 1e5)),
+            radius: 0.0,
+        };
+
+        assert_eq!(
+            OrbitalElements::of(&Gravity::UNIVERSAL, &escaping, &parent()),
+            None
+        );
+
+        let coincident = Body {
+            id: BodyId(2),
+            ..parent()
+        };
+
+        assert_eq!(
+            OrbitalElements::of(&Gravity::UNIVERSAL, &coincident, &parent()),
+            None
+        );
+    }
+}
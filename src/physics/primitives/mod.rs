@@ -1,7 +1,8 @@
 use core::ops;
 
+use serde::{Deserialize, Serialize};
+
 pub use position::*;
-use std::cmp::Ordering;
 use std::ops::{Add, Mul};
 pub use vector::*;
 
@@ -11,7 +12,7 @@ mod vector;
 pub type Scalar = f64;
 
 // Scalars
-#[derive(Debug, PartialEq, Copy, Clone)]
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
 pub struct TemporalDuration(pub Scalar);
 
 impl Mul<Scalar> for TemporalDuration {
@@ -30,7 +31,7 @@ impl Add<TemporalDuration> for TemporalDuration {
     }
 }
 
-#[derive(Debug, PartialEq, Copy, Clone)]
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
 pub struct Mass(pub Scalar);
 
 impl Add<Mass> for Mass {
@@ -49,7 +50,7 @@ impl Mul<Velocity> for Mass {
     }
 }
 
-#[derive(Debug, PartialEq, Copy, Clone)]
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
 pub struct Velocity(pub Vector2D);
 
 impl ops::Mul<TemporalDuration> for Velocity {
@@ -100,6 +101,19 @@ impl ops::Add<Momentum> for Momentum {
     }
 }
 
+/// Angular momentum in the plane, which only has a component along the z axis.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct AngularMomentum(pub Scalar);
+
+impl ops::Add<AngularMomentum> for AngularMomentum {
+    type Output = AngularMomentum;
+
+    fn add(self, rhs: AngularMomentum) -> Self::Output {
+        AngularMomentum(self.0 + rhs.0)
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
 pub struct Energy(pub Scalar);
 
 impl ops::Add<Energy> for Energy {
@@ -109,3 +123,11 @@ impl ops::Add<Energy> for Energy {
         Energy(self.0 + rhs.0)
     }
 }
+
+impl ops::Sub<Energy> for Energy {
+    type Output = Energy;
+
+    fn sub(self, rhs: Energy) -> Self::Output {
+        Energy(self.0 - rhs.0)
+    }
+}
@@ -1,9 +1,11 @@
 use core::ops;
 
+use serde::{Deserialize, Serialize};
+
 use crate::physics::primitives::vector::Vector2D;
 
 // Vector quantities
-#[derive(Debug, PartialEq, Copy, Clone)]
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
 pub struct Position(pub Vector2D);
 
 impl ops::Add<Position> for Position {
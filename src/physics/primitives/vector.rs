@@ -1,11 +1,12 @@
 use core::ops;
 use std::fmt;
 use std::fmt::{Display, Formatter};
-use std::ops::Mul;
+
+use serde::{Deserialize, Serialize};
 
 use crate::physics::primitives::Scalar;
 
-#[derive(Debug, PartialEq, Copy, Clone)]
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
 pub struct Vector2D {
     pub x: Scalar,
     pub y: Scalar,
@@ -24,6 +25,15 @@ impl Vector2D {
         (self.x.powi(2) + self.y.powi(2)).sqrt()
     }
 
+    pub fn dot(self: Vector2D, rhs: Vector2D) -> Scalar {
+        self.x * rhs.x + self.y * rhs.y
+    }
+
+    /// The z component of the cross product of two vectors in the plane.
+    pub fn cross(self: Vector2D, rhs: Vector2D) -> Scalar {
+        self.x * rhs.y - self.y * rhs.x
+    }
+
     pub fn unit(self: Vector2D) -> Vector2D {
         let magnitude = self.magnitude();
 
@@ -91,7 +101,7 @@ impl ops::Mul<Vector2D> for Scalar {
 
 impl Display for Vector2D {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "[{}, {}]", self.x.to_string(), self.y.to_string())
+        write!(f, "[{}, {}]", self.x, self.y)
     }
 }
 
@@ -112,6 +122,11 @@ mod tests {
         )
     }
 
+    #[test]
+    fn vector_cross_product() {
+        assert_eq!(Vector2D::new(1.0, 2.0).cross(Vector2D::new(3.0, 4.0)), -2.0)
+    }
+
     #[test]
     fn vector_subtraction() {
         assert_eq!(
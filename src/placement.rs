@@ -0,0 +1,103 @@
+use n_body_rust::{Body, BodyId, Mass, Position, Scalar, Velocity};
+
+/// A dragged body moves the length of the drag in this many seconds.
+pub const DRAG_DURATION: Scalar = 1e5;
+
+/// How much each press of the mass keys changes the mass of placed bodies.
+const MASS_FACTOR: Scalar = 10.0;
+
+/// Placing new bodies by pressing where they should start and dragging out their velocity.
+pub struct Placement {
+    /// Whether clicks place bodies, rather than selecting them.
+    pub enabled: bool,
+    pub mass: Mass,
+    /// In kg/m³, which sets the radius of placed bodies.
+    pub density: Scalar,
+    start: Option<Position>,
+}
+
+impl Placement {
+    pub fn new(mass: Mass, density: Scalar) -> Placement {
+        Placement {
+            enabled: false,
+            mass,
+            density,
+            start: None,
+        }
+    }
+
+    pub fn toggle(self: &mut Placement) {
+        self.enabled = !self.enabled;
+        self.start = None;
+    }
+
+    pub fn heavier(self: &mut Placement) {
+        self.mass = Mass(self.mass.0 * MASS_FACTOR);
+    }
+
+    pub fn lighter(self: &mut Placement) {
+        self.mass = Mass(self.mass.0 / MASS_FACTOR);
+    }
+
+    /// Starts placing a body at `position`.
+    pub fn begin(self: &mut Placement, position: Position) {
+        self.start = Some(position);
+    }
+
+    /// The body that would be placed if the drag ended at `position`.
+    pub fn preview(self: &Placement, position: Position) -> Option<Body> {
+        let start = self.start?;
+
+        Some(Body {
+            id: BodyId::default(),
+            mass: self.mass,
+            position: start,
+            velocity: Velocity((position - start).0 / DRAG_DURATION),
+            radius: Body::radius_for_density(self.mass, self.density),
+        })
+    }
+
+    /// Ends the drag at `position`, returning the body to add to the universe.
+    pub fn finish(self: &mut Placement, position: Position) -> Option<Body> {
+        let body = self.preview(position);
+        self.start = None;
+        body
+    }
+
+    /// What placing is doing, when the cursor is at `position`.
+    pub fn describe(self: &Placement, position: Position) -> String {
+        match self.preview(position) {
+            Some(body) => format!(
+                "placing {:e} kg at {:.3e} m/s",
+                self.mass.0,
+                body.velocity.0.magnitude()
+            ),
+            None => format!("placing: click and drag to add {:e} kg", self.mass.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::placement::{Placement, DRAG_DURATION};
+    use n_body_rust::{Mass, Position, Vector2D, Velocity};
+
+    #[test]
+    fn test_dragging_sets_the_velocity() {
+        let mut placement = Placement::new(Mass(1e20), 1400.0);
+        let start = Position(Vector2D::new(1e6, 2e6));
+        let end = Position(Vector2D::new(
+            1e6 + 3.0 * DRAG_DURATION,
+            2e6 - DRAG_DURATION,
+        ));
+
+        placement.heavier();
+        placement.begin(start);
+        let body = placement.finish(end).unwrap();
+
+        assert_eq!(body.mass, Mass(1e21));
+        assert_eq!(body.position, start);
+        assert_eq!(body.velocity, Velocity(Vector2D::new(3.0, -1.0)));
+        assert_eq!(placement.finish(end), None);
+    }
+}
@@ -0,0 +1,120 @@
+use n_body_rust::{Scalar, TemporalDuration};
+
+/// How much each press of the speed keys speeds up or slows down the simulation.
+const SPEED_FACTOR: Scalar = 2.0;
+
+/// How fast simulated time passes in the viewer, and whether it is passing at all.
+pub struct Playback {
+    /// Simulated seconds per real second. Negative when running backwards.
+    pub time_scale: Scalar,
+    pub paused: bool,
+    /// Whether the numerical method can run backwards.
+    pub reversible: bool,
+    /// Whether running backwards was asked for but the numerical method cannot.
+    pub reverse_refused: bool,
+    single_step_requested: bool,
+}
+
+impl Playback {
+    pub fn new(time_scale: Scalar, reversible: bool) -> Playback {
+        Playback {
+            time_scale,
+            paused: false,
+            reversible,
+            reverse_refused: false,
+            single_step_requested: false,
+        }
+    }
+
+    pub fn toggle_pause(self: &mut Playback) {
+        self.paused = !self.paused;
+    }
+
+    /// Pauses, and advances by exactly one step at the next update.
+    pub fn single_step(self: &mut Playback) {
+        self.paused = true;
+        self.single_step_requested = true;
+    }
+
+    pub fn speed_up(self: &mut Playback) {
+        self.time_scale *= SPEED_FACTOR;
+    }
+
+    pub fn slow_down(self: &mut Playback) {
+        self.time_scale /= SPEED_FACTOR;
+    }
+
+    /// Switches between running forwards and backwards, if the numerical method can.
+    pub fn reverse(self: &mut Playback) {
+        if self.reversible {
+            self.time_scale = -self.time_scale;
+        } else {
+            self.reverse_refused = true;
+        }
+    }
+
+    /// The simulated time to step through when `ui_dt` of real time has passed, or `None` if
+    /// the universe should stay as it is.
+    pub fn dt_for(self: &mut Playback, ui_dt: TemporalDuration) -> Option<TemporalDuration> {
+        if self.paused && !self.single_step_requested {
+            return None;
+        }
+
+        self.single_step_requested = false;
+        Some(ui_dt * self.time_scale)
+    }
+
+    pub fn describe(self: &Playback) -> String {
+        let direction = if self.time_scale < 0.0 {
+            "backwards"
+        } else {
+            "forwards"
+        };
+
+        format!(
+            "{} {} at {:e}×",
+            if self.paused { "paused" } else { "running" },
+            direction,
+            self.time_scale.abs()
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::playback::Playback;
+    use n_body_rust::TemporalDuration;
+
+    #[test]
+    fn test_single_step_advances_once_then_stays_paused() {
+        let mut playback = Playback::new(100.0, false);
+        let ui_dt = TemporalDuration(0.5);
+
+        playback.toggle_pause();
+        assert_eq!(playback.dt_for(ui_dt), None);
+
+        playback.single_step();
+        assert_eq!(playback.dt_for(ui_dt), Some(TemporalDuration(50.0)));
+        assert_eq!(playback.dt_for(ui_dt), None);
+
+        playback.toggle_pause();
+        assert_eq!(playback.dt_for(ui_dt), Some(TemporalDuration(50.0)));
+    }
+
+    #[test]
+    fn test_only_reversible_methods_run_backwards() {
+        let ui_dt = TemporalDuration(0.5);
+
+        let mut irreversible = Playback::new(100.0, false);
+        irreversible.reverse();
+        assert!(irreversible.reverse_refused);
+        assert_eq!(irreversible.dt_for(ui_dt), Some(TemporalDuration(50.0)));
+
+        let mut reversible = Playback::new(100.0, true);
+        reversible.speed_up();
+        reversible.reverse();
+        assert!(!reversible.reverse_refused);
+        assert_eq!(reversible.dt_for(ui_dt), Some(TemporalDuration(-100.0)));
+        assert_eq!(reversible.describe(), "running backwards at 2e2×");
+    }
+}
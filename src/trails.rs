@@ -0,0 +1,184 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use n_body_rust::{BodyId, CollisionEvent, Position, Universe};
+
+/// Where each body has recently been, so that its orbit can be drawn behind it.
+pub struct Trails {
+    /// The most points kept for each body.
+    pub length: usize,
+    /// How many steps pass between recorded points.
+    pub spacing: usize,
+    steps_since_recorded: usize,
+    histories: HashMap<BodyId, VecDeque<Position>>,
+}
+
+impl Trails {
+    pub fn new(length: usize, spacing: usize) -> Trails {
+        Trails {
+            length,
+            spacing: spacing.max(1),
+            steps_since_recorded: 0,
+            histories: HashMap::new(),
+        }
+    }
+
+    /// Notes where every body is after a step through `collisions`.
+    ///
+    /// A body that merges into another keeps its trail, joined to where the merged body formed,
+    /// and the trail then shortens until it is gone. The merged body carries on the trail of the
+    /// body whose id it kept.
+    pub fn record(self: &mut Trails, universe: &Universe, collisions: &[CollisionEvent]) {
+        for collision in collisions {
+            if let [result] = collision.result.as_slice() {
+                for participant in &collision.participants {
+                    if let Some(history) = self.histories.get_mut(&participant.id) {
+                        if participant.id != result.id {
+                            push_bounded(history, result.position, self.length);
+                        }
+                    }
+                }
+            }
+        }
+
+        self.steps_since_recorded += 1;
+        if self.steps_since_recorded < self.spacing {
+            return;
+        }
+        self.steps_since_recorded = 0;
+
+        let present: HashSet<BodyId> = universe.bodies.iter().map(|body| body.id).collect();
+
+        for body in &universe.bodies {
+            let history = self.histories.entry(body.id).or_default();
+            push_bounded(history, body.position, self.length);
+        }
+
+        for (id, history) in self.histories.iter_mut() {
+            if !present.contains(id) {
+                history.pop_front();
+            }
+        }
+
+        self.histories.retain(|_, history| !history.is_empty());
+    }
+
+    /// The points recorded for a body, oldest first.
+    #[cfg(test)]
+    pub fn of(self: &Trails, id: BodyId) -> Option<&VecDeque<Position>> {
+        self.histories.get(&id)
+    }
+
+    /// Every trail, along with the id of the body that left it.
+    pub fn iter(self: &Trails) -> impl Iterator<Item = (&BodyId, &VecDeque<Position>)> {
+        self.histories.iter()
+    }
+}
+
+/// Adds `position` to the end of `history`, dropping the oldest points beyond `length`.
+fn push_bounded(history: &mut VecDeque<Position>, position: Position, length: usize) {
+    history.push_back(position);
+
+    while history.len() > length {
+        history.pop_front();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::trails::Trails;
+    use n_body_rust::{
+        universes, Body, BodyId, DirectSummation, Engine, Leapfrog, Mass, Merge, Position,
+        TemporalDuration, Universe, Vector2D, Velocity,
+    };
+
+    fn engine() -> Engine<Leapfrog, DirectSummation, Merge> {
+        Engine {
+            numerical_method: Leapfrog,
+            force_solver: DirectSummation,
+            collision_policy: Merge,
+        }
+    }
+
+    fn step(universe: &Universe, trails: &mut Trails) -> Universe {
+        let (universe, report) =
+            engine().step_forward_with_report(universe, TemporalDuration(600.0));
+        trails.record(&universe, &report.collisions);
+        universe
+    }
+
+    #[test]
+    fn test_trails_are_bounded_and_spaced() {
+        let mut universe = universes::pluto_and_charon();
+        let charon = universe.bodies[1].id;
+        let mut trails = Trails::new(5, 3);
+
+        let mut positions = vec![];
+        for _ in 0..30 {
+            universe = step(&universe, &mut trails);
+            positions.push(universe.body(charon).unwrap().position);
+        }
+
+        let expected: Vec<Position> = positions.into_iter().skip(2).step_by(3).skip(5).collect();
+        assert_eq!(trails.of(charon).unwrap(), &expected);
+    }
+
+    #[test]
+    fn test_absorbed_trails_join_the_merged_body_then_fade() {
+        let mut universe = universes::pluto_and_charon();
+        let charon = universe.bodies[1];
+        let mut trails = Trails::new(10, 1);
+
+        for _ in 0..3 {
+            universe = step(&universe, &mut trails);
+        }
+
+        // A heavier body lands on Charon, absorbing it and keeping its own id
+        let impact_point = universe.body(charon.id).unwrap().position;
+        let impactor = universe.add_body(Body {
+            id: BodyId::default(),
+            mass: Mass(1e23),
+            velocity: Velocity(Vector2D::zero()),
+            ..*universe.body(charon.id).unwrap()
+        });
+        universe = step(&universe, &mut trails);
+
+        let charon_trail = trails.of(charon.id).unwrap();
+        assert_eq!(charon_trail.len(), 3);
+        assert!((*charon_trail.back().unwrap() - impact_point).0.magnitude() < 1e-6);
+        assert_eq!(
+            trails.of(impactor).unwrap(),
+            &[universe.body(impactor).unwrap().position]
+        );
+
+        for _ in 0..3 {
+            universe = step(&universe, &mut trails);
+        }
+
+        assert_eq!(trails.of(charon.id), None);
+        assert_eq!(trails.of(impactor).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_joining_the_merged_body_keeps_trails_within_their_length() {
+        let mut universe = universes::pluto_and_charon();
+        let charon = universe.bodies[1];
+        let mut trails = Trails::new(3, 1);
+
+        for _ in 0..5 {
+            universe = step(&universe, &mut trails);
+        }
+
+        let impact_point = universe.body(charon.id).unwrap().position;
+        universe.add_body(Body {
+            id: BodyId::default(),
+            mass: Mass(1e23),
+            velocity: Velocity(Vector2D::zero()),
+            ..*universe.body(charon.id).unwrap()
+        });
+        step(&universe, &mut trails);
+
+        let charon_trail = trails.of(charon.id).unwrap();
+        assert_eq!(charon_trail.len(), 2);
+        assert!((*charon_trail.back().unwrap() - impact_point).0.magnitude() < 1e-6);
+    }
+}
@@ -1,63 +1,180 @@
-use crate::engine::universe::{Body, Universe};
-use crate::physics::gravity::{GravitationalConstant, Gravity};
+use crate::engine::universe::{Body, BodyId, Universe};
+use crate::physics::gravity::{GravitationalConstant, Gravity, Softening};
+use crate::physics::orbit::{OrbitDirection, OrbitalElements};
 use crate::physics::primitives::*;
-use core::iter;
-use rand::distributions::uniform::{SampleBorrow, SampleUniform, UniformSampler};
-use rand::distributions::{Distribution, Standard, Uniform};
-use rand::Rng;
+use rand::distributions::Distribution;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 
+/// Pluto with Charon on its observed orbit, a = 19,591 km and e = 0.0002.
 pub fn pluto_and_charon() -> Universe {
-    let g = GravitationalConstant::UNIVERSAL;
+    let gravity = Gravity {
+        gravitational_constant: GravitationalConstant::UNIVERSAL,
+        softening: Softening::None,
+    };
 
     let pluto = Body {
+        id: BodyId::default(),
         mass: Mass(1.303e22),
         position: Position(Vector2D::zero()),
         velocity: Velocity(Vector2D::zero()),
+        radius: 1_188.3e3,
     };
 
-    let charon = Body {
-        mass: Mass(1.586e21),
-        position: Position(Vector2D::new(19587000.0, 0.0)),
-        velocity: Velocity(Vector2D::new(0.0, 210.0)),
-    };
+    let charon = OrbitalElements {
+        semi_major_axis: 19_591e3,
+        eccentricity: 0.0002,
+        argument_of_periapsis: 0.0,
+        mean_anomaly: 0.0,
+        direction: OrbitDirection::Anticlockwise,
+    }
+    .body_orbiting(&gravity, &pluto, Mass(1.586e21), 606e3);
+
+    let mut universe = Universe::new(gravity, vec![pluto, charon]);
+    universe.name_body(universe.bodies[0].id, "Pluto");
+    universe.name_body(universe.bodies[1].id, "Charon");
+    universe
+}
+
+/// The random number generator the seeded generators use. The same seed always produces the same
+/// sequence of numbers, on every platform.
+pub fn seeded_rng(seed: u64) -> ChaCha8Rng {
+    ChaCha8Rng::seed_from_u64(seed)
+}
 
+/// As `random`, but drawing from a generator seeded with `seed`, which is recorded in the universe.
+/// The same seed always gives the same universe.
+pub fn seeded_random(
+    seed: u64,
+    n_bodies: u16,
+    mass_distribution: impl Distribution<Scalar>,
+    position_distribution: impl Distribution<Vector2D>,
+    velocity_distribution: impl Distribution<Vector2D>,
+    density: Scalar,
+) -> Universe {
     Universe {
-        gravity: Gravity {
-            gravitational_constant: g,
-        },
-        bodies: vec![pluto, charon],
-        age: TemporalDuration(0.0),
+        seed: Some(seed),
+        ..random(
+            &mut seeded_rng(seed),
+            n_bodies,
+            mass_distribution,
+            position_distribution,
+            velocity_distribution,
+            density,
+        )
     }
 }
 
-pub fn random(
+/// Scatters `n_bodies` bodies with masses, positions and velocities drawn from the given
+/// distributions using `rng`. Every body has the same `density`, in kg/m³.
+pub fn random<R: Rng + ?Sized>(
+    rng: &mut R,
     n_bodies: u16,
     mass_distribution: impl Distribution<Scalar>,
     position_distribution: impl Distribution<Vector2D>,
     velocity_distribution: impl Distribution<Vector2D>,
+    density: Scalar,
 ) -> Universe {
-    let masses = mass_distribution.sample_iter(rand::thread_rng());
-    let positions = position_distribution.sample_iter(rand::thread_rng());
-    let velocities = velocity_distribution.sample_iter(rand::thread_rng());
-
-    let bodies = masses
-        .zip(positions)
-        .zip(velocities)
-        .take(n_bodies as usize)
-        .map(|((mass, position_vector), velocity_vector)| Body {
-            mass: Mass(mass),
-            position: Position(position_vector),
-            velocity: Velocity(velocity_vector),
+    let bodies = (0..n_bodies)
+        .map(|_| {
+            let mass = Mass(mass_distribution.sample(rng));
+
+            Body {
+                id: BodyId::default(),
+                mass,
+                position: Position(position_distribution.sample(rng)),
+                velocity: Velocity(velocity_distribution.sample(rng)),
+                radius: Body::radius_for_density(mass, density),
+            }
         })
         .collect();
 
+    Universe::new(Gravity::UNIVERSAL, bodies)
+}
+
+/// A Plummer sphere of `n_bodies` equal masses totalling `total_mass`, a common model of a star
+/// cluster in equilibrium. Half of the mass lies within about 1.3 times `scale_radius` of the
+/// centre.
+///
+/// Radii follow the Plummer mass profile and speeds its distribution function, with both laid out
+/// in the plane. Velocities are then scaled so that the universe starts with a virial ratio of
+/// exactly one under `gravity`, softening included, which also accounts for the missing third
+/// dimension. A lone body is simply left at rest. Every body has the same `density`, in kg/m³.
+pub fn plummer_sphere<R: Rng + ?Sized>(
+    rng: &mut R,
+    gravity: Gravity,
+    n_bodies: u16,
+    total_mass: Mass,
+    scale_radius: Scalar,
+    density: Scalar,
+) -> Universe {
+    // Radii beyond about 39 scale radii are too sparse to be worth sampling
+    const MAX_ENCLOSED_MASS_FRACTION: Scalar = 0.999;
+
+    let mass = Mass(total_mass.0 / Scalar::from(n_bodies));
+
+    let bodies: Vec<Body> = (0..n_bodies)
+        .map(|_| {
+            let enclosed_mass_fraction: Scalar = rng.gen_range(0.0..MAX_ENCLOSED_MASS_FRACTION);
+            let radius = scale_radius / (enclosed_mass_fraction.powf(-2.0 / 3.0) - 1.0).sqrt();
+
+            let escape_speed_fraction = plummer_escape_speed_fraction(rng);
+            // Only proportional to the escape speed, as velocities are rescaled below
+            let speed = escape_speed_fraction * (radius.powi(2) + scale_radius.powi(2)).powf(-0.25);
+
+            Body {
+                id: BodyId::default(),
+                mass,
+                position: Position(random_direction(rng) * radius),
+                velocity: Velocity(random_direction(rng) * speed),
+                radius: Body::radius_for_density(mass, density),
+            }
+        })
+        .collect();
+
+    let universe = Universe::new(gravity, bodies);
+
+    let centre_of_mass = universe.centre_of_mass();
+    let centre_of_mass_velocity = universe.centre_of_mass_velocity();
+    // A lone body has no potential energy for its motion to balance
+    let velocity_scale = if universe.potential_energy().0 == 0.0 {
+        1.0
+    } else {
+        universe.virial_ratio().sqrt().recip()
+    };
+
     Universe {
-        gravity: Gravity::UNIVERSAL,
-        bodies,
-        age: TemporalDuration(0.0),
+        bodies: universe
+            .bodies
+            .iter()
+            .map(|body| Body {
+                position: body.position - centre_of_mass,
+                velocity: Velocity((body.velocity.0 - centre_of_mass_velocity.0) * velocity_scale),
+                ..*body
+            })
+            .collect(),
+        ..universe
     }
 }
 
+/// A speed as a fraction of the escape speed, following the Plummer distribution function
+/// q²(1 - q²)^3.5, by rejection sampling.
+fn plummer_escape_speed_fraction<R: Rng + ?Sized>(rng: &mut R) -> Scalar {
+    // The distribution function peaks at about 0.092
+    loop {
+        let (q, g): (Scalar, Scalar) = (rng.gen_range(0.0..1.0), rng.gen_range(0.0..0.1));
+        if g < q.powi(2) * (1.0 - q.powi(2)).powf(3.5) {
+            return q;
+        }
+    }
+}
+
+fn random_direction<R: Rng + ?Sized>(rng: &mut R) -> Vector2D {
+    let theta: Scalar = rng.gen_range(0.0..2.0 * std::f64::consts::PI);
+
+    Vector2D::new(Scalar::cos(theta), Scalar::sin(theta))
+}
+
 pub struct BoxedVector2DDistribution {
     pub x_min: Scalar,
     pub x_max: Scalar,
@@ -68,8 +185,8 @@ pub struct BoxedVector2DDistribution {
 impl Distribution<Vector2D> for BoxedVector2DDistribution {
     fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Vector2D {
         Vector2D {
-            x: rng.gen_range((self.x_min..self.x_max)),
-            y: rng.gen_range((self.y_min..self.y_max)),
+            x: rng.gen_range(self.x_min..self.x_max),
+            y: rng.gen_range(self.y_min..self.y_max),
         }
     }
 }
@@ -81,8 +198,8 @@ pub struct CircularVector2DDistribution {
 
 impl Distribution<Vector2D> for CircularVector2DDistribution {
     fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Vector2D {
-        let theta: Scalar = rng.gen_range((0.0..2.0 * std::f64::consts::PI));
-        let r: Scalar = rng.gen_range((self.magnitude_min..self.magnitude_max));
+        let theta: Scalar = rng.gen_range(0.0..2.0 * std::f64::consts::PI);
+        let r: Scalar = rng.gen_range(self.magnitude_min..self.magnitude_max);
 
         Vector2D {
             x: r * Scalar::cos(theta),
@@ -90,3 +207,129 @@ impl Distribution<Vector2D> for CircularVector2DDistribution {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use rand::distributions::Uniform;
+
+    use crate::engine::universe::Universe;
+    use crate::physics::gravity::{Gravity, Softening};
+    use crate::physics::primitives::{Mass, Scalar, Vector2D};
+    use crate::universes::{
+        plummer_escape_speed_fraction, plummer_sphere, seeded_random, seeded_rng,
+        BoxedVector2DDistribution, CircularVector2DDistribution,
+    };
+
+    fn generate(seed: u64) -> Universe {
+        seeded_random(
+            seed,
+            100,
+            Uniform::new(0.0, 1e21),
+            BoxedVector2DDistribution {
+                x_min: -1e8,
+                x_max: 1e8,
+                y_min: -1e8,
+                y_max: 1e8,
+            },
+            CircularVector2DDistribution {
+                magnitude_min: 0.0,
+                magnitude_max: 5e2,
+            },
+            1400.0,
+        )
+    }
+
+    #[test]
+    fn same_seed_gives_same_universe() {
+        let universe = generate(42);
+
+        assert_eq!(universe, generate(42));
+        assert_eq!(universe.seed, Some(42));
+        assert_ne!(universe.bodies, generate(43).bodies);
+    }
+
+    #[test]
+    fn plummer_sphere_follows_the_plummer_mass_profile() {
+        let scale_radius = 1e8;
+        let gravity = Gravity {
+            softening: Softening::Plummer { length: 1e7 },
+            ..Gravity::UNIVERSAL
+        };
+        let universe = plummer_sphere(
+            &mut seeded_rng(1),
+            gravity,
+            1000,
+            Mass(1e24),
+            scale_radius,
+            1400.0,
+        );
+
+        assert_eq!(universe.gravity, gravity);
+        let total_mass: Scalar = universe.bodies.iter().map(|body| body.mass.0).sum();
+        assert!((total_mass - 1e24).abs() < 1e12);
+        assert!(universe.momentum().0.magnitude() < 1e-6 * 1e24);
+
+        let mut radii: Vec<Scalar> = universe
+            .bodies
+            .iter()
+            .map(|body| body.position.0.magnitude())
+            .collect();
+        radii.sort_by(|left, right| left.partial_cmp(right).unwrap());
+        let half_mass_radius = radii[radii.len() / 2];
+
+        // The half mass radius of a Plummer sphere is a / sqrt(2^(2/3) - 1)
+        let expected = scale_radius / (Scalar::powf(2.0, 2.0 / 3.0) - 1.0).sqrt();
+        assert!(
+            (half_mass_radius / expected - 1.0).abs() < 0.1,
+            "half mass radius {}",
+            half_mass_radius
+        );
+    }
+
+    #[test]
+    fn plummer_speeds_follow_the_distribution_function() {
+        let mut rng = seeded_rng(2);
+        let samples: Vec<Scalar> = (0..20_000)
+            .map(|_| plummer_escape_speed_fraction(&mut rng))
+            .collect();
+
+        // Moments of q²(1 - q²)^3.5 on [0, 1], by the midpoint rule
+        let distribution = |q: Scalar| q.powi(2) * (1.0 - q.powi(2)).powf(3.5);
+        let moment = |power: i32| {
+            (0..10_000)
+                .map(|i| (i as Scalar + 0.5) / 10_000.0)
+                .map(|q| q.powi(power) * distribution(q))
+                .sum::<Scalar>()
+        };
+
+        for power in 1..=2 {
+            let expected = moment(power) / moment(0);
+            let sampled =
+                samples.iter().map(|q| q.powi(power)).sum::<Scalar>() / samples.len() as Scalar;
+
+            assert!(
+                (sampled / expected - 1.0).abs() < 0.02,
+                "moment {}: sampled {}, expected {}",
+                power,
+                sampled,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn plummer_sphere_of_one_body_is_at_rest() {
+        let universe = plummer_sphere(
+            &mut seeded_rng(3),
+            Gravity::UNIVERSAL,
+            1,
+            Mass(1e24),
+            1e8,
+            1400.0,
+        );
+
+        assert_eq!(universe.bodies.len(), 1);
+        assert_eq!(universe.bodies[0].velocity.0, Vector2D::zero());
+        assert!(universe.total_energy().0.is_finite());
+    }
+}
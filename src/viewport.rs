@@ -1,13 +1,7 @@
-use crate::physics::primitives::{Position, Scalar, Vector2D};
-use graphics::math::{transform_pos, transform_vec, Vec2d};
+use n_body_rust::{Body, Position, Scalar, Vector2D};
 use piston::RenderArgs;
 
-impl From<Vector2D> for Vec2d {
-    fn from(vector2d: Vector2D) -> Self {
-        [vector2d.x, vector2d.y]
-    }
-}
-
+#[derive(Debug, PartialEq, Copy, Clone)]
 pub struct Viewport {
     pub x_min: Scalar,
     pub x_max: Scalar,
@@ -16,14 +10,6 @@ pub struct Viewport {
 }
 
 impl Viewport {
-    pub fn zero() -> Viewport {
-        Viewport::around_origin(0.0, 0.0)
-    }
-
-    pub fn around_origin(x_size: Scalar, y_size: Scalar) -> Viewport {
-        Viewport::around(Position(Vector2D::zero()), x_size, y_size)
-    }
-
     pub fn square_around(centre: Position, size: Scalar) -> Viewport {
         Viewport::around(centre, size, size)
     }
@@ -51,7 +37,7 @@ impl Viewport {
 
         let normalised_position_within_viewport = (
             (position.0.x - window_origin_in_viewport.0) / window_size_in_viewport.0,
-            -1.0 * ((position.0.y - window_origin_in_viewport.1) / window_size_in_viewport.1),
+            -((position.0.y - window_origin_in_viewport.1) / window_size_in_viewport.1),
         );
 
         let [window_x_size, window_y_size] = render_args.window_size;
@@ -61,13 +47,115 @@ impl Viewport {
             normalised_position_within_viewport.1 * window_y_size,
         )
     }
+
+    /// The position in the viewport shown at `window_position`, in a window of `window_size`. The
+    /// inverse of `convert_for_window`.
+    pub fn convert_from_window(
+        self: &Viewport,
+        window_size: [f64; 2],
+        window_position: [f64; 2],
+    ) -> Position {
+        let [window_x_size, window_y_size] = window_size;
+
+        Position(Vector2D::new(
+            self.x_min + window_position[0] / window_x_size * (self.x_max - self.x_min),
+            self.y_max - window_position[1] / window_y_size * (self.y_max - self.y_min),
+        ))
+    }
+
+    pub fn centre(self: &Viewport) -> Position {
+        Position(Vector2D::new(
+            (self.x_min + self.x_max) / 2.0,
+            (self.y_min + self.y_max) / 2.0,
+        ))
+    }
+
+    /// The same view, moved by `offset`.
+    pub fn shifted_by(self: &Viewport, offset: Vector2D) -> Viewport {
+        Viewport {
+            x_min: self.x_min + offset.x,
+            x_max: self.x_max + offset.x,
+            y_min: self.y_min + offset.y,
+            y_max: self.y_max + offset.y,
+        }
+    }
+
+    /// The view seen after dragging its contents by `window_offset`, in a window of `window_size`.
+    pub fn panned_by(self: &Viewport, window_size: [f64; 2], window_offset: [f64; 2]) -> Viewport {
+        let origin = self.convert_from_window(window_size, [0.0, 0.0]);
+        let dragged_origin = self.convert_from_window(window_size, window_offset);
+
+        self.shifted_by((origin - dragged_origin).0)
+    }
+
+    /// The view magnified by `factor`, keeping `fixed_point` where it is in the window. A factor
+    /// above one zooms in.
+    pub fn zoomed_around(self: &Viewport, fixed_point: Position, factor: Scalar) -> Viewport {
+        let Position(point) = fixed_point;
+
+        Viewport {
+            x_min: point.x + (self.x_min - point.x) / factor,
+            x_max: point.x + (self.x_max - point.x) / factor,
+            y_min: point.y + (self.y_min - point.y) / factor,
+            y_max: point.y + (self.y_max - point.y) / factor,
+        }
+    }
+
+    /// The smallest square view centred on the middle of `bodies` that shows all of them whole,
+    /// with a margin of `margin` times its size around them, or `fallback_size` across if they take
+    /// up no room at all. `None` if there are no bodies.
+    pub fn square_fitting(
+        bodies: &[Body],
+        margin: Scalar,
+        fallback_size: Scalar,
+    ) -> Option<Viewport> {
+        let (x_min, x_max, y_min, y_max) = bodies.iter().fold(
+            (Scalar::MAX, Scalar::MIN, Scalar::MAX, Scalar::MIN),
+            |(x_min, x_max, y_min, y_max), body| {
+                let Position(position) = body.position;
+                (
+                    x_min.min(position.x - body.radius),
+                    x_max.max(position.x + body.radius),
+                    y_min.min(position.y - body.radius),
+                    y_max.max(position.y + body.radius),
+                )
+            },
+        );
+
+        if bodies.is_empty() {
+            return None;
+        }
+
+        let centre = Position(Vector2D::new((x_min + x_max) / 2.0, (y_min + y_max) / 2.0));
+        let size = Scalar::max(x_max - x_min, y_max - y_min) * (1.0 + 2.0 * margin);
+
+        // Point masses all in one place have no size of their own to fit
+        Some(Viewport::square_around(
+            centre,
+            if size > 0.0 { size } else { fallback_size },
+        ))
+    }
+
+    /// The length in the window of a horizontal `length` in the viewport.
+    pub fn length_for_window(self: &Viewport, render_args: &RenderArgs, length: Scalar) -> f64 {
+        length / (self.x_max - self.x_min) * render_args.window_size[0]
+    }
 }
 
+#[cfg(test)]
 mod test {
-    use crate::physics::primitives::{Position, Vector2D};
     use crate::viewport::Viewport;
+    use n_body_rust::{Body, BodyId, Mass, Position, Vector2D, Velocity};
     use piston::RenderArgs;
 
+    fn render_args(window_size: [f64; 2]) -> RenderArgs {
+        RenderArgs {
+            ext_dt: 0.0,
+            window_size,
+            draw_size: [window_size[0] as u32, window_size[1] as u32],
+        }
+    }
+
     #[test]
     fn test_convert_for_window_viewport_around_origin() {
         let viewport = Viewport {
@@ -117,4 +205,115 @@ mod test {
             (75.0, 125.0)
         );
     }
+
+    #[test]
+    fn test_length_for_window() {
+        let viewport = Viewport {
+            x_min: 10.0,
+            x_max: 50.0,
+            y_min: 10.0,
+            y_max: 50.0,
+        };
+
+        let render_args = RenderArgs {
+            ext_dt: 0.0,
+            window_size: [200.0, 200.0],
+            draw_size: [200, 200],
+        };
+
+        assert_eq!(viewport.length_for_window(&render_args, 4.0), 20.0);
+    }
+
+    #[test]
+    fn test_convert_from_window_inverts_convert_for_window() {
+        let viewport = Viewport {
+            x_min: -30.0,
+            x_max: 50.0,
+            y_min: 10.0,
+            y_max: 50.0,
+        };
+        let render_args = render_args([200.0, 100.0]);
+
+        for &(x, y) in &[(0.0, 0.0), (25.0, 25.0), (-30.0, 50.0), (120.0, -7.5)] {
+            let position = Position(Vector2D::new(x, y));
+            let (window_x, window_y) = viewport.convert_for_window(&render_args, position);
+
+            assert_eq!(
+                viewport.convert_from_window(render_args.window_size, [window_x, window_y]),
+                position
+            );
+        }
+    }
+
+    #[test]
+    fn test_zoomed_around_keeps_the_point_in_place() {
+        let viewport = Viewport::square_around(Position(Vector2D::new(10.0, 10.0)), 40.0);
+        let render_args = render_args([200.0, 200.0]);
+        let point = Position(Vector2D::new(20.0, 0.0));
+
+        let zoomed = viewport.zoomed_around(point, 2.0);
+
+        assert_eq!(zoomed.x_max - zoomed.x_min, 20.0);
+        assert_eq!(
+            zoomed.convert_for_window(&render_args, point),
+            viewport.convert_for_window(&render_args, point)
+        );
+    }
+
+    #[test]
+    fn test_panned_by_moves_contents_with_the_cursor() {
+        let viewport = Viewport::square_around(Position(Vector2D::zero()), 100.0);
+        let render_args = render_args([200.0, 200.0]);
+        let point = Position(Vector2D::new(10.0, 10.0));
+
+        let panned = viewport.panned_by(render_args.window_size, [20.0, -10.0]);
+
+        let (x, y) = viewport.convert_for_window(&render_args, point);
+        assert_eq!(
+            panned.convert_for_window(&render_args, point),
+            (x + 20.0, y - 10.0)
+        );
+    }
+
+    #[test]
+    fn test_square_fitting_shows_every_body_whole() {
+        let body = |x, y, radius| Body {
+            id: BodyId::default(),
+            mass: Mass(1.0),
+            position: Position(Vector2D::new(x, y)),
+            velocity: Velocity(Vector2D::zero()),
+            radius,
+        };
+
+        assert_eq!(Viewport::square_fitting(&[], 0.1, 100.0), None);
+        assert_eq!(
+            Viewport::square_fitting(&[body(0.0, 0.0, 1.0), body(10.0, 4.0, 2.0)], 0.0, 100.0),
+            Some(Viewport {
+                x_min: -1.0,
+                x_max: 12.0,
+                y_min: -4.0,
+                y_max: 9.0,
+            })
+        );
+    }
+
+    #[test]
+    fn test_square_fitting_a_single_body() {
+        let body = |radius| Body {
+            id: BodyId::default(),
+            mass: Mass(1.0),
+            position: Position(Vector2D::new(5.0, -3.0)),
+            velocity: Velocity(Vector2D::zero()),
+            radius,
+        };
+
+        assert_eq!(
+            Viewport::square_fitting(&[body(2.0)], 0.0, 100.0),
+            Some(Viewport::square_around(body(2.0).position, 4.0))
+        );
+        assert_eq!(
+            Viewport::square_fitting(&[body(0.0)], 0.0, 100.0),
+            Some(Viewport::square_around(body(0.0).position, 100.0))
+        );
+    }
 }